@@ -0,0 +1,274 @@
+use bevy::{
+    audio::{PlaybackMode, Volume},
+    platform::collections::HashMap,
+    prelude::*,
+};
+
+use crate::{
+    blueprint::BlueprintStatus,
+    jobs::LevelComplete,
+    player::{Player, Splash},
+};
+
+pub struct AudioPlugin;
+
+impl Plugin for AudioPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<AudioVolumes>()
+            .add_message::<PlaySound>()
+            .add_systems(Startup, setup_audio_system)
+            .add_systems(
+                Update,
+                (
+                    footstep_sounds_system,
+                    blueprint_sounds_system,
+                    event_sounds_system,
+                    play_sounds_system,
+                    music_volume_system,
+                )
+                    .chain(),
+            );
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum SoundEffect {
+    Footstep,
+    Pickup,
+    Place,
+    /// The held items can't be placed here.
+    Blocked,
+    BlueprintValid,
+    BlueprintInvalid,
+    LevelComplete,
+    Splash,
+}
+
+impl SoundEffect {
+    const ALL: [SoundEffect; 8] = [
+        SoundEffect::Footstep,
+        SoundEffect::Pickup,
+        SoundEffect::Place,
+        SoundEffect::Blocked,
+        SoundEffect::BlueprintValid,
+        SoundEffect::BlueprintInvalid,
+        SoundEffect::LevelComplete,
+        SoundEffect::Splash,
+    ];
+
+    fn path(self) -> &'static str {
+        match self {
+            SoundEffect::Footstep => "audio/footstep.wav",
+            SoundEffect::Pickup => "audio/pickup.wav",
+            SoundEffect::Place => "audio/place.wav",
+            SoundEffect::Blocked => "audio/blocked.wav",
+            SoundEffect::BlueprintValid => "audio/blueprint_valid.wav",
+            SoundEffect::BlueprintInvalid => "audio/blueprint_invalid.wav",
+            SoundEffect::LevelComplete => "audio/level_complete.wav",
+            SoundEffect::Splash => "audio/splash.wav",
+        }
+    }
+
+    fn category(self) -> VolumeCategory {
+        match self {
+            SoundEffect::Footstep | SoundEffect::Splash => VolumeCategory::Ambient,
+            _ => VolumeCategory::Effects,
+        }
+    }
+}
+
+/// Send this to play a sound effect.
+#[derive(Message, Copy, Clone, Debug)]
+pub struct PlaySound(pub SoundEffect);
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum VolumeCategory {
+    Music,
+    Effects,
+    /// Quiet, frequent sounds like footsteps.
+    Ambient,
+}
+
+/// Volumes from 0 to 1. Each category is scaled by `master`.
+#[derive(Resource, Clone, Debug)]
+pub struct AudioVolumes {
+    pub master: f32,
+    pub music: f32,
+    pub effects: f32,
+    pub ambient: f32,
+}
+
+impl Default for AudioVolumes {
+    fn default() -> Self {
+        Self {
+            master: 1.,
+            music: 0.5,
+            effects: 0.8,
+            ambient: 0.5,
+        }
+    }
+}
+
+impl AudioVolumes {
+    pub fn volume(&self, category: VolumeCategory) -> Volume {
+        let category_volume = match category {
+            VolumeCategory::Music => self.music,
+            VolumeCategory::Effects => self.effects,
+            VolumeCategory::Ambient => self.ambient,
+        };
+        Volume::Linear(self.master * category_volume)
+    }
+}
+
+#[derive(Resource)]
+pub struct SoundHandles {
+    pub effects: HashMap<SoundEffect, Handle<AudioSource>>,
+}
+
+#[derive(Component)]
+pub struct Music;
+
+pub fn setup_audio_system(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    volumes: Res<AudioVolumes>,
+) {
+    let effects = SoundEffect::ALL
+        .into_iter()
+        .map(|effect| (effect, asset_server.load(effect.path())))
+        .collect();
+    commands.insert_resource(SoundHandles { effects });
+
+    commands.spawn((
+        Music,
+        AudioPlayer::new(asset_server.load("audio/music.wav")),
+        PlaybackSettings {
+            mode: PlaybackMode::Loop,
+            volume: volumes.volume(VolumeCategory::Music),
+            ..default()
+        },
+    ));
+}
+
+pub fn play_sounds_system(
+    mut commands: Commands,
+    mut sounds: MessageReader<PlaySound>,
+    handles: Res<SoundHandles>,
+    volumes: Res<AudioVolumes>,
+) {
+    for PlaySound(effect) in sounds.read() {
+        let Some(handle) = handles.effects.get(effect) else {
+            continue;
+        };
+        commands.spawn((
+            AudioPlayer::new(handle.clone()),
+            PlaybackSettings::DESPAWN.with_volume(volumes.volume(effect.category())),
+        ));
+    }
+}
+
+pub fn music_volume_system(
+    volumes: Res<AudioVolumes>,
+    mut music: Query<&mut AudioSink, With<Music>>,
+) {
+    if !volumes.is_changed() {
+        return;
+    }
+    for mut sink in music.iter_mut() {
+        sink.set_volume(volumes.volume(VolumeCategory::Music));
+    }
+}
+
+/// How far the player walks between footsteps.
+const STRIDE: f32 = 0.6;
+
+pub fn footstep_sounds_system(
+    time: Res<Time>,
+    players: Query<&Player>,
+    mut walked: Local<f32>,
+    mut sounds: MessageWriter<PlaySound>,
+) {
+    for player in players.iter() {
+        if player.in_water {
+            continue;
+        }
+        *walked += player.velocity.length() * time.delta_secs();
+        if *walked >= STRIDE {
+            *walked = 0.;
+            sounds.write(PlaySound(SoundEffect::Footstep));
+        }
+    }
+}
+
+/// The chime for a blueprint going from `was_valid` to `is_valid`. A blueprint seen for the first
+/// time is silent, so the level does not chime as it loads.
+pub fn blueprint_sound(was_valid: Option<bool>, is_valid: bool) -> Option<SoundEffect> {
+    match was_valid {
+        Some(false) if is_valid => Some(SoundEffect::BlueprintValid),
+        Some(true) if !is_valid => Some(SoundEffect::BlueprintInvalid),
+        _ => None,
+    }
+}
+
+/// Chimes when a blueprint's room becomes valid, or stops being valid.
+pub fn blueprint_sounds_system(
+    blueprints: Query<(Entity, &BlueprintStatus)>,
+    mut was_valid: Local<HashMap<Entity, bool>>,
+    mut sounds: MessageWriter<PlaySound>,
+) {
+    for (blueprint_entity, status) in blueprints.iter() {
+        let is_valid = status.is_valid();
+        if let Some(effect) =
+            blueprint_sound(was_valid.insert(blueprint_entity, is_valid), is_valid)
+        {
+            sounds.write(PlaySound(effect));
+        }
+    }
+}
+
+pub fn event_sounds_system(
+    mut splashes: MessageReader<Splash>,
+    mut level_complete: MessageReader<LevelComplete>,
+    mut sounds: MessageWriter<PlaySound>,
+) {
+    for _ in splashes.read() {
+        sounds.write(PlaySound(SoundEffect::Splash));
+    }
+    for _ in level_complete.read() {
+        sounds.write(PlaySound(SoundEffect::LevelComplete));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn volumes_are_scaled_by_master() {
+        let volumes = AudioVolumes {
+            master: 0.5,
+            music: 0.4,
+            effects: 1.,
+            ambient: 0.,
+        };
+        assert_eq!(volumes.volume(VolumeCategory::Music).to_linear(), 0.2);
+        assert_eq!(volumes.volume(VolumeCategory::Effects).to_linear(), 0.5);
+        assert_eq!(volumes.volume(VolumeCategory::Ambient).to_linear(), 0.);
+    }
+
+    #[test]
+    fn blueprints_chime_only_when_validity_changes() {
+        assert_eq!(blueprint_sound(None, true), None);
+        assert_eq!(blueprint_sound(None, false), None);
+        assert_eq!(
+            blueprint_sound(Some(false), true),
+            Some(SoundEffect::BlueprintValid)
+        );
+        assert_eq!(
+            blueprint_sound(Some(true), false),
+            Some(SoundEffect::BlueprintInvalid)
+        );
+        assert_eq!(blueprint_sound(Some(true), true), None);
+        assert_eq!(blueprint_sound(Some(false), false), None);
+    }
+}
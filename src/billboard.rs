@@ -1,23 +1,42 @@
-use bevy::{platform::collections::HashMap, prelude::*};
+use bevy::{light::NotShadowCaster, platform::collections::HashMap, prelude::*};
 
 use crate::player::Player;
 
 #[derive(Resource)]
 pub struct BillboardMaterials {
-    pub materials: HashMap<String, Handle<StandardMaterial>>,
-    pub mesh: Handle<Mesh>,
+    pub materials: HashMap<BillboardMaterialKey, Handle<StandardMaterial>>,
+    pub meshes: HashMap<BillboardMeshKey, Handle<Mesh>>,
+    pub blob_shadow_mesh: Handle<Mesh>,
+    pub blob_shadow_material: Handle<StandardMaterial>,
 }
 
+/// Billboards which look the same share a material.
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct BillboardMaterialKey {
+    pub image: String,
+    pub frame: Option<AtlasFrame>,
+    pub tint: [u8; 4],
+    pub alpha: BillboardAlpha,
+    pub lit: bool,
+}
+
+/// The bits of a billboard's size and anchor, which determine its mesh.
+pub type BillboardMeshKey = [u32; 4];
+
 pub struct BillboardPlugin;
 
 impl Plugin for BillboardPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Startup, setup_billboards_system)
+        app.insert_resource(BillboardShadowSettings::default())
+            .add_systems(Startup, setup_billboards_system)
             .add_systems(
                 Update,
                 (
+                    animate_billboards_system.before(add_mesh_system),
                     add_mesh_system,
+                    apply_billboard_shadows_system,
                     track_billboards_system.after(crate::player::move_player_system),
+                    update_blob_shadows_system.after(track_billboards_system),
                 ),
             );
     }
@@ -26,30 +45,287 @@ impl Plugin for BillboardPlugin {
 #[derive(Component)]
 pub struct Billboard {
     pub image: String,
+    /// When set, only this cell of the image is shown.
+    pub frame: Option<AtlasFrame>,
+    /// Width and height in world units.
+    pub size: Vec2,
+    /// The point of the image which sits at the entity's translation, where (0, 0) is the centre
+    /// of the image and (0, -0.5) is the middle of its bottom edge.
+    pub anchor: Vec2,
+    pub tint: Color,
+    pub alpha: BillboardAlpha,
+    /// Whether the billboard is shaded by the scene's lights. Unlit billboards always show the
+    /// image's own colors.
+    pub lit: bool,
+    pub shadow: BillboardShadow,
+}
+
+impl Billboard {
+    pub const ANCHOR_CENTER: Vec2 = Vec2::ZERO;
+    pub const ANCHOR_BOTTOM_CENTER: Vec2 = Vec2::new(0., -0.5);
+
+    fn material_key(&self) -> BillboardMaterialKey {
+        BillboardMaterialKey {
+            image: self.image.clone(),
+            frame: self.frame,
+            tint: self.tint.to_srgba().to_u8_array(),
+            alpha: self.alpha,
+            lit: self.lit,
+        }
+    }
+
+    /// The kind of shadow to draw, after falling back to blob shadows if real ones are disabled.
+    fn effective_shadow(&self, settings: &BillboardShadowSettings) -> BillboardShadow {
+        if self.shadow == BillboardShadow::Cast && !settings.cast_shadows {
+            BillboardShadow::Blob
+        } else {
+            self.shadow
+        }
+    }
+
+    fn mesh_key(&self) -> BillboardMeshKey {
+        [
+            self.size.x.to_bits(),
+            self.size.y.to_bits(),
+            self.anchor.x.to_bits(),
+            self.anchor.y.to_bits(),
+        ]
+    }
+}
+
+impl Default for Billboard {
+    fn default() -> Self {
+        Self {
+            image: String::new(),
+            frame: None,
+            size: Vec2::ONE,
+            anchor: Billboard::ANCHOR_BOTTOM_CENTER,
+            tint: Color::WHITE,
+            alpha: BillboardAlpha::Mask,
+            lit: false,
+            shadow: BillboardShadow::None,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub enum BillboardShadow {
+    #[default]
+    None,
+    /// Casts a real shadow from the directional light, shaped like the image.
+    Cast,
+    /// Draws a soft dark circle on the ground underneath the billboard.
+    Blob,
+}
+
+/// Real billboard shadows are replaced by blob shadows when `cast_shadows` is off, which is the
+/// default for wasm builds.
+#[derive(Resource)]
+pub struct BillboardShadowSettings {
+    pub cast_shadows: bool,
+}
+
+impl Default for BillboardShadowSettings {
+    fn default() -> Self {
+        Self {
+            cast_shadows: !cfg!(target_arch = "wasm32"),
+        }
+    }
+}
+
+/// The direction an entity is heading in, on the ground plane (x, z).
+///
+/// Billboards with a `Facing` are mirrored to match whether they head to the left or the right of
+/// the camera, unless they have `DirectionalSprites`.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct Facing {
+    pub heading: Vec2,
+    /// The last mirroring applied to the billboard, kept while heading straight towards or away
+    /// from the camera.
+    pub mirror: f32,
+}
+
+impl Facing {
+    pub fn new(heading: Vec2) -> Self {
+        Self {
+            heading,
+            mirror: 1.,
+        }
+    }
+}
+
+impl Default for Facing {
+    fn default() -> Self {
+        Facing::new(Vec2::X)
+    }
+}
+
+/// Images of an entity seen from 4 or 8 evenly spaced directions. The first image is shown when
+/// the entity is heading towards the camera, and the others follow as its heading turns from +x
+/// towards +z, so with 4 images the second is shown heading to the left of the screen.
+#[derive(Component, Clone, Debug)]
+pub struct DirectionalSprites {
+    pub images: Vec<String>,
+}
+
+impl DirectionalSprites {
+    fn image_for(&self, heading: Vec2, to_camera: Vec2) -> Option<&str> {
+        if self.images.is_empty() || heading == Vec2::ZERO || to_camera == Vec2::ZERO {
+            return None;
+        }
+        let step = std::f32::consts::TAU / self.images.len() as f32;
+        let turns = (to_camera.angle_to(heading) / step).round() as i32;
+        let index = turns.rem_euclid(self.images.len() as i32) as usize;
+        Some(&self.images[index])
+    }
+}
+
+/// A blob shadow which follows the `owner` billboard around.
+#[derive(Component)]
+pub struct BlobShadow {
+    pub owner: Entity,
+}
+
+/// Marks a billboard that currently has a blob shadow.
+#[derive(Component)]
+pub struct HasBlobShadow(pub Entity);
+
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub enum BillboardAlpha {
+    /// Pixels are either fully drawn or not drawn at all.
+    #[default]
+    Mask,
+    /// Pixels are blended with what's behind them, for translucent billboards.
+    Blend,
+}
+
+impl BillboardAlpha {
+    fn alpha_mode(self) -> AlphaMode {
+        match self {
+            BillboardAlpha::Mask => AlphaMode::Mask(0.5),
+            BillboardAlpha::Blend => AlphaMode::Blend,
+        }
+    }
+}
+
+/// A cell of an image which is split into a grid of equally-sized frames, numbered left to right
+/// and then top to bottom.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct AtlasFrame {
+    pub columns: u32,
+    pub rows: u32,
+    pub index: u32,
+}
+
+impl AtlasFrame {
+    fn uv_transform(self) -> bevy::math::Affine2 {
+        let size = Vec2::new(1. / self.columns as f32, 1. / self.rows as f32);
+        let cell = Vec2::new(
+            (self.index % self.columns) as f32,
+            (self.index / self.columns) as f32,
+        );
+        bevy::math::Affine2::from_scale_angle_translation(size, 0., cell * size)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct SpriteClip {
+    /// Frame indices into the sheet, played in order and looped.
+    pub frames: Vec<u32>,
+    pub seconds_per_frame: f32,
+}
+
+/// Plays named clips from a sprite sheet by updating the `frame` of the entity's `Billboard`.
+#[derive(Component, Clone, Debug)]
+pub struct AnimatedBillboard {
+    pub columns: u32,
+    pub rows: u32,
+    pub clips: HashMap<String, SpriteClip>,
+    pub clip: String,
+    /// Time since the current clip started.
+    pub elapsed: f32,
+}
+
+impl AnimatedBillboard {
+    /// Switches to the named clip, restarting it only if it wasn't already playing.
+    pub fn play(&mut self, clip: &str) {
+        if self.clip != clip {
+            self.clip = clip.to_string();
+            self.elapsed = 0.;
+        }
+    }
+
+    pub fn current_frame(&self) -> Option<AtlasFrame> {
+        let clip = self.clips.get(&self.clip)?;
+        if clip.frames.is_empty() {
+            return None;
+        }
+        let step = (self.elapsed / clip.seconds_per_frame.max(0.001)) as usize;
+        Some(AtlasFrame {
+            columns: self.columns,
+            rows: self.rows,
+            index: clip.frames[step % clip.frames.len()],
+        })
+    }
 }
 
 #[derive(Component)]
 pub struct BillboardCamera;
 
+pub fn animate_billboards_system(
+    time: Res<Time>,
+    mut billboards: Query<(&mut AnimatedBillboard, &mut Billboard)>,
+) {
+    let dt = time.delta_secs();
+    for (mut animation, mut billboard) in billboards.iter_mut() {
+        animation.elapsed += dt;
+        let frame = animation.current_frame();
+        if billboard.frame != frame {
+            billboard.frame = frame;
+        }
+    }
+}
+
 fn add_mesh_system(
     mut commands: Commands,
-    billboards: Query<(Entity, &Billboard), Without<Mesh3d>>,
+    billboards: Query<(Entity, &Billboard), Or<(Without<Mesh3d>, Changed<Billboard>)>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
+    mut meshes: ResMut<Assets<Mesh>>,
     mut cached_materials: ResMut<BillboardMaterials>,
     asset_server: Res<AssetServer>,
 ) {
     for (billboard_entity, billboard) in billboards.iter() {
-        let mesh = cached_materials.mesh.clone();
+        let mesh = cached_materials
+            .meshes
+            .entry(billboard.mesh_key())
+            .or_insert_with(|| {
+                // The billboard's local +Y points down the image, so the anchor is flipped to match.
+                let offset = Vec3::new(
+                    -billboard.anchor.x * billboard.size.x,
+                    billboard.anchor.y * billboard.size.y,
+                    0.,
+                );
+                meshes.add(
+                    Mesh::from(Plane3d::new(-Vec3::Z, billboard.size / 2.)).translated_by(offset),
+                )
+            })
+            .clone();
         let material = cached_materials
             .materials
-            .entry(billboard.image.clone())
+            .entry(billboard.material_key())
             .or_insert_with(|| {
                 materials.add(StandardMaterial {
-                    unlit: true,
+                    unlit: !billboard.lit,
+                    perceptual_roughness: 1.,
+                    base_color: billboard.tint,
                     base_color_texture: Some(asset_server.load(&billboard.image)),
-                    alpha_mode: AlphaMode::Mask(0.5),
+                    alpha_mode: billboard.alpha.alpha_mode(),
                     double_sided: true,
                     cull_mode: None,
+                    uv_transform: billboard
+                        .frame
+                        .map(AtlasFrame::uv_transform)
+                        .unwrap_or_default(),
                     ..default()
                 })
             });
@@ -60,11 +336,88 @@ fn add_mesh_system(
     }
 }
 
-fn setup_billboards_system(mut commands: Commands, mut meshes: ResMut<Assets<Mesh>>) {
-    let mesh = meshes.add(Plane3d::new(-Vec3::Z, Vec2::splat(0.5)));
+fn apply_billboard_shadows_system(
+    mut commands: Commands,
+    settings: Res<BillboardShadowSettings>,
+    cached_materials: Res<BillboardMaterials>,
+    billboards: Query<(Entity, Ref<Billboard>, Option<&HasBlobShadow>)>,
+) {
+    for (billboard_entity, billboard, blob_shadow) in billboards.iter() {
+        if !billboard.is_changed() && !settings.is_changed() {
+            continue;
+        }
+        let shadow = billboard.effective_shadow(&settings);
+
+        if shadow == BillboardShadow::Cast {
+            commands
+                .entity(billboard_entity)
+                .remove::<NotShadowCaster>();
+        } else {
+            commands.entity(billboard_entity).insert(NotShadowCaster);
+        }
+
+        match (shadow == BillboardShadow::Blob, blob_shadow) {
+            (true, None) => {
+                let blob_shadow_entity = commands
+                    .spawn((
+                        BlobShadow {
+                            owner: billboard_entity,
+                        },
+                        Mesh3d(cached_materials.blob_shadow_mesh.clone()),
+                        MeshMaterial3d(cached_materials.blob_shadow_material.clone()),
+                        NotShadowCaster,
+                        Transform::from_scale(Vec3::ZERO),
+                    ))
+                    .id();
+                commands
+                    .entity(billboard_entity)
+                    .insert(HasBlobShadow(blob_shadow_entity));
+            }
+            (false, Some(blob_shadow)) => {
+                commands.entity(blob_shadow.0).despawn();
+                commands.entity(billboard_entity).remove::<HasBlobShadow>();
+            }
+            _ => {}
+        }
+    }
+}
+
+fn update_blob_shadows_system(
+    mut commands: Commands,
+    mut blob_shadows: Query<(Entity, &BlobShadow, &mut Transform)>,
+    owners: Query<(&Transform, &Billboard), Without<BlobShadow>>,
+) {
+    for (blob_shadow_entity, blob_shadow, mut blob_shadow_transform) in blob_shadows.iter_mut() {
+        let Ok((owner_transform, owner_billboard)) = owners.get(blob_shadow.owner) else {
+            commands.entity(blob_shadow_entity).despawn();
+            continue;
+        };
+        blob_shadow_transform.translation =
+            owner_transform.translation * Vec3::new(1., 0., 1.) + Vec3::Y * 0.01;
+        blob_shadow_transform.scale = Vec3::splat(owner_billboard.size.x * owner_transform.scale.x);
+    }
+}
+
+fn setup_billboards_system(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let blob_shadow_mesh = meshes.add(
+        Mesh::from(Circle::new(0.4))
+            .rotated_by(Quat::from_rotation_x(-std::f32::consts::FRAC_PI_2)),
+    );
+    let blob_shadow_material = materials.add(StandardMaterial {
+        unlit: true,
+        base_color: Color::linear_rgba(0., 0., 0., 0.35),
+        alpha_mode: AlphaMode::Blend,
+        ..default()
+    });
     commands.insert_resource(BillboardMaterials {
-        mesh,
+        meshes: HashMap::new(),
         materials: HashMap::new(),
+        blob_shadow_mesh,
+        blob_shadow_material,
     });
 }
 
@@ -72,25 +425,41 @@ fn track_billboards_system(
     time: Res<Time>,
     camera: Query<&Transform, With<BillboardCamera>>,
     mut billboards: Query<
-        (&mut Transform, Option<&Player>),
-        (Without<BillboardCamera>, With<Billboard>),
+        (
+            &mut Transform,
+            &mut Billboard,
+            Option<&mut Facing>,
+            Option<&DirectionalSprites>,
+            Option<&Player>,
+        ),
+        Without<BillboardCamera>,
     >,
 ) {
     let total_time = time.elapsed_secs();
     let Ok(camera) = camera.single() else {
         return;
     };
-    for (mut billboard_transform, player) in billboards.iter_mut() {
-        let facing_direction = match player {
-            Some(player) => {
-                if player.facing_direction == 0. {
-                    1.
-                } else {
-                    player.facing_direction
+    let camera_right = camera.right().xz();
+    for (mut billboard_transform, mut billboard, facing, directional_sprites, player) in
+        billboards.iter_mut()
+    {
+        let mut facing_direction = 1.;
+        if let Some(mut facing) = facing {
+            if let Some(directional_sprites) = directional_sprites {
+                let to_camera = (camera.translation - billboard_transform.translation).xz();
+                if let Some(image) = directional_sprites.image_for(facing.heading, to_camera)
+                    && billboard.image != image
+                {
+                    billboard.image = image.to_string();
                 }
+            } else {
+                let side = facing.heading.dot(camera_right);
+                if side.abs() > 0.01 {
+                    facing.mirror = side.signum();
+                }
+                facing_direction = facing.mirror;
             }
-            None => 1.,
-        };
+        }
 
         let wiggle = match player {
             Some(player) => player.velocity.length().min(1.) * (total_time * 19.).cos() * 0.1,
@@ -104,3 +473,87 @@ fn track_billboards_system(
         billboard_transform.rotate_local_z(wiggle);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+
+    fn directions(count: usize) -> DirectionalSprites {
+        DirectionalSprites {
+            images: (0..count).map(|index| index.to_string()).collect(),
+        }
+    }
+
+    /// The image shown when heading `degrees` counterclockwise from the direction to the camera.
+    fn image_at(sprites: &DirectionalSprites, degrees: f32) -> &str {
+        let to_camera = Vec2::new(0.6, 0.8);
+        let heading = Vec2::from_angle(degrees.to_radians()).rotate(to_camera);
+        sprites.image_for(heading, to_camera).unwrap()
+    }
+
+    #[test]
+    fn four_directions() {
+        let sprites = directions(4);
+        let images: Vec<&str> = [0., 50., 90., 140., 180., -90., -40.]
+            .map(|degrees| image_at(&sprites, degrees))
+            .to_vec();
+        assert_eq!(images, vec!["0", "1", "1", "2", "2", "3", "0"]);
+        // Either side of straight away from the camera is the back image.
+        assert_eq!(image_at(&sprites, 179.), "2");
+        assert_eq!(image_at(&sprites, -179.), "2");
+        assert_eq!(image_at(&sprites, -180.), "2");
+    }
+
+    #[test]
+    fn eight_directions() {
+        let sprites = directions(8);
+        let images: Vec<&str> = [0., 45., 100., 135., 150., -45., -100., -150.]
+            .map(|degrees| image_at(&sprites, degrees))
+            .to_vec();
+        assert_eq!(images, vec!["0", "1", "2", "3", "3", "7", "6", "5"]);
+        assert_eq!(image_at(&sprites, 175.), "4");
+        assert_eq!(image_at(&sprites, -175.), "4");
+    }
+
+    #[test]
+    fn no_image_without_a_heading() {
+        assert_eq!(directions(4).image_for(Vec2::ZERO, Vec2::Y), None);
+        assert_eq!(directions(0).image_for(Vec2::X, Vec2::Y), None);
+    }
+
+    #[test]
+    fn animation_advances_a_frame_per_step() {
+        let mut world = World::new();
+        world.insert_resource(Time::<()>::default());
+        let clip = SpriteClip {
+            frames: vec![4, 5, 6],
+            seconds_per_frame: 0.1,
+        };
+        let billboard = world
+            .spawn((
+                AnimatedBillboard {
+                    columns: 4,
+                    rows: 2,
+                    clips: HashMap::from_iter([("walk".to_string(), clip)]),
+                    clip: "walk".to_string(),
+                    elapsed: 0.,
+                },
+                Billboard::default(),
+            ))
+            .id();
+
+        let mut frames = Vec::new();
+        for _ in 0..4 {
+            world
+                .resource_mut::<Time>()
+                .advance_by(std::time::Duration::from_millis(105));
+            world.run_system_once(animate_billboards_system).unwrap();
+            let frame = world.get::<Billboard>(billboard).unwrap().frame;
+            frames.push(frame.map(|frame| frame.index));
+        }
+        // The clip loops back to its first frame.
+        assert_eq!(frames, vec![Some(5), Some(6), Some(4), Some(5)]);
+    }
+}
@@ -0,0 +1,50 @@
+//! Converts a level between a level image and level text, by the files' extensions.
+//!
+//! ```sh
+//! cargo run --bin convert_level -- assets/level.png level.txt
+//! cargo run --bin convert_level -- level.txt assets/level.png
+//! ```
+
+use bevy_github_ci_template::level::Level;
+
+const USAGE: &str = "usage:
+    convert_level <level.png> <level.txt>
+    convert_level <level.txt> <level.png>";
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let args: Vec<&str> = args.iter().map(|arg| arg.as_str()).collect();
+
+    let [in_path, out_path] = args.as_slice() else {
+        eprintln!("{}", USAGE);
+        std::process::exit(2);
+    };
+
+    let level = match Level::load(in_path) {
+        Ok(level) => level,
+        Err(err) => {
+            eprintln!("cannot load {}: {}", in_path, err);
+            std::process::exit(1);
+        }
+    };
+
+    if !out_path.ends_with(".txt") {
+        // Level images glue adjacent items together, and have nowhere to put requirements.
+        if level.glue != level.adjacent_glue() {
+            eprintln!(
+                "warning: {} can't keep the glue groups, so adjacent fences and bridges will be glued instead",
+                out_path
+            );
+        }
+        if !level.blueprints.is_empty() {
+            eprintln!(
+                "warning: {} can't keep the blueprint requirements",
+                out_path
+            );
+        }
+    }
+    if let Err(err) = level.save(out_path) {
+        eprintln!("cannot write {}: {}", out_path, err);
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,56 @@
+//! Writes the meshes the game generates for a room image pair or a level image to an OBJ file, so
+//! they can be inspected in external tools.
+//!
+//! ```sh
+//! cargo run --bin export_mesh -- room assets/room4.png assets/room4_collider.png room4.obj
+//! cargo run --bin export_mesh -- level assets/level.png level.obj
+//! ```
+
+use bevy_github_ci_template::{
+    export::{export_level, export_room},
+    level::Level,
+    rooms::{build_room_geometry, open_room_layer},
+};
+
+const USAGE: &str = "usage:
+    export_mesh room <room.png> <room_collider.png> <out.obj>
+    export_mesh level <level.png> <out.obj>";
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let args: Vec<&str> = args.iter().map(|arg| arg.as_str()).collect();
+
+    let (export, out_path) = match args.as_slice() {
+        ["room", room_path, room_collider_path, out_path] => {
+            let [room_layer, room_collider_layer] =
+                [*room_path, *room_collider_path].map(|path| match open_room_layer(path) {
+                    Ok(layer) => layer,
+                    Err(err) => {
+                        eprintln!("cannot load {}: {}", path, err);
+                        std::process::exit(1);
+                    }
+                });
+            let room = build_room_geometry(&room_layer, &room_collider_layer);
+            (export_room(&room), *out_path)
+        }
+        ["level", level_path, out_path] => {
+            let level = match Level::load(level_path) {
+                Ok(level) => level,
+                Err(err) => {
+                    eprintln!("cannot load {}: {}", level_path, err);
+                    std::process::exit(1);
+                }
+            };
+            (export_level(&level), *out_path)
+        }
+        _ => {
+            eprintln!("{}", USAGE);
+            std::process::exit(2);
+        }
+    };
+
+    if let Err(err) = std::fs::write(out_path, export.finish()) {
+        eprintln!("cannot write {}: {}", out_path, err);
+        std::process::exit(1);
+    }
+}
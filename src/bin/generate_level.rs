@@ -0,0 +1,46 @@
+//! Generates a level from a seed, or from today's daily challenge seed, and saves it as level text or
+//! a level image.
+//!
+//! ```sh
+//! cargo run --release --bin generate_level -- 42 level.txt
+//! cargo run --release --bin generate_level -- daily level.txt
+//! ```
+
+use bevy_github_ci_template::generator::{GeneratorOptions, daily_seed, generate_level};
+
+const USAGE: &str = "usage:
+    generate_level <seed|daily> <out.txt|out.png>";
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let args: Vec<&str> = args.iter().map(|arg| arg.as_str()).collect();
+
+    let (seed, out_path) = match args.as_slice() {
+        ["daily", out_path] => (daily_seed(), *out_path),
+        [seed, out_path] => match seed.parse::<u64>() {
+            Ok(seed) => (seed, *out_path),
+            Err(_) => {
+                eprintln!("{}", USAGE);
+                std::process::exit(2);
+            }
+        },
+        _ => {
+            eprintln!("{}", USAGE);
+            std::process::exit(2);
+        }
+    };
+
+    let Some(generated) = generate_level(seed, &GeneratorOptions::default()) else {
+        eprintln!("no solvable level found for seed {}", seed);
+        std::process::exit(1);
+    };
+    if let Err(err) = generated.level.save(out_path) {
+        eprintln!("cannot write {}: {}", out_path, err);
+        std::process::exit(1);
+    }
+    println!(
+        "seed {}: solved in {} moves",
+        seed,
+        generated.solution.len()
+    );
+}
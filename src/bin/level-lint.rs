@@ -0,0 +1,66 @@
+//! Checks a level image, level text or a room image pair for mistakes, printing each one with the
+//! pixel it is at. Exits with an error if any are errors rather than warnings.
+//!
+//! ```sh
+//! cargo run --bin level-lint -- level assets/level.png
+//! cargo run --bin level-lint -- level level.txt
+//! cargo run --bin level-lint -- room assets/room4.png assets/room4_collider.png
+//! ```
+
+use bevy_github_ci_template::{
+    level::Level,
+    lint::{Severity, lint_level, lint_room},
+};
+
+const USAGE: &str = "usage:
+    level-lint level <level.png|level.txt>
+    level-lint room <room.png> <room_collider.png>";
+
+fn open_image(path: &str) -> image::DynamicImage {
+    match image::open(path) {
+        Ok(image) => image,
+        Err(err) => {
+            eprintln!("cannot load {}: {}", path, err);
+            std::process::exit(2);
+        }
+    }
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let args: Vec<&str> = args.iter().map(|arg| arg.as_str()).collect();
+
+    let (path, diagnostics) = match args.as_slice() {
+        ["level", level_path] => {
+            let level = match Level::load(level_path) {
+                Ok(level) => level,
+                Err(err) => {
+                    eprintln!("cannot load {}: {}", level_path, err);
+                    std::process::exit(2);
+                }
+            };
+            (*level_path, lint_level(&level))
+        }
+        ["room", room_path, room_collider_path] => (
+            *room_path,
+            lint_room(&open_image(room_path), &open_image(room_collider_path)),
+        ),
+        _ => {
+            eprintln!("{}", USAGE);
+            std::process::exit(2);
+        }
+    };
+
+    for diagnostic in &diagnostics {
+        println!("{}: {}", path, diagnostic);
+    }
+    let errors = diagnostics
+        .iter()
+        .filter(|diagnostic| diagnostic.severity == Severity::Error)
+        .count();
+    let warnings = diagnostics.len() - errors;
+    println!("{}: {} errors, {} warnings", path, errors, warnings);
+    if errors > 0 {
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,80 @@
+//! Checks that a level can be finished, by searching for moves that satisfy all of its blueprints.
+//!
+//! ```sh
+//! cargo run --release --bin solve_level -- assets/level.png assets/jobs.ron
+//! ```
+
+use bevy_github_ci_template::{
+    jobs::Jobs,
+    level::Level,
+    solver::{Puzzle, SolveResult},
+};
+
+const USAGE: &str = "usage:
+    solve_level <level.png> [jobs.ron]";
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let args: Vec<&str> = args.iter().map(|arg| arg.as_str()).collect();
+
+    let (level_path, jobs_path) = match args.as_slice() {
+        [level_path] => (*level_path, None),
+        [level_path, jobs_path] => (*level_path, Some(*jobs_path)),
+        _ => {
+            eprintln!("{}", USAGE);
+            std::process::exit(2);
+        }
+    };
+
+    let level = match Level::load(level_path) {
+        Ok(level) => level,
+        Err(err) => {
+            eprintln!("cannot load {}: {}", level_path, err);
+            std::process::exit(2);
+        }
+    };
+    let jobs = match jobs_path.map(Jobs::load) {
+        Some(Ok(jobs)) => jobs.specs,
+        Some(Err(err)) => {
+            eprintln!("cannot load {}: {}", jobs_path.unwrap(), err);
+            std::process::exit(2);
+        }
+        None => Vec::new(),
+    };
+    let puzzle = match Puzzle::new(&level, &jobs) {
+        Ok(puzzle) => puzzle,
+        Err(err) => {
+            eprintln!("cannot solve {}: {}", level_path, err);
+            std::process::exit(2);
+        }
+    };
+
+    match puzzle.solve() {
+        SolveResult::Solved(moves) => {
+            for (index, solution_move) in moves.iter().enumerate() {
+                println!(
+                    "{:>3}. pick up ({}, {}), turn {} times, put down at ({}, {}) from ({}, {})",
+                    index + 1,
+                    solution_move.pick_up.x,
+                    solution_move.pick_up.y,
+                    solution_move.quarter_turns,
+                    solution_move.place_at.x,
+                    solution_move.place_at.y,
+                    solution_move.stand.x,
+                    solution_move.stand.y,
+                );
+            }
+            println!("solved in {} moves", moves.len());
+        }
+        SolveResult::Unsolvable => {
+            println!(
+                "unsolvable: no arrangement of the items the player can reach satisfies every blueprint"
+            );
+            std::process::exit(1);
+        }
+        SolveResult::GaveUp { states } => {
+            println!("gave up after {} states", states);
+            std::process::exit(1);
+        }
+    }
+}
@@ -1,8 +1,16 @@
 use std::collections::VecDeque;
 
-use bevy::{platform::collections::HashMap, prelude::*};
-
-use crate::player::{Player, Wall, Water};
+use bevy::{
+    platform::collections::{HashMap, HashSet},
+    prelude::*,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ground::GroundGrid,
+    player::{Player, Wall},
+    settings::Settings,
+};
 
 #[derive(Component)]
 pub struct Blueprint;
@@ -10,6 +18,42 @@ pub struct Blueprint;
 #[derive(Component)]
 pub struct Door;
 
+/// What a client wants from the room around a blueprint, on top of it being closed with a door.
+#[derive(Component, Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct BlueprintRequirements {
+    /// The fewest floor cells the room can have.
+    pub min_size: usize,
+    pub max_size: Option<usize>,
+    pub min_doors: usize,
+}
+
+impl BlueprintRequirements {
+    /// A line describing each requirement, and whether the evaluated room meets it.
+    pub fn check(&self, evaluation: &BlueprintEvaluation) -> Vec<(String, bool)> {
+        let mut checks = Vec::new();
+        if self.min_size > 0 {
+            checks.push((
+                format!("At least {} tiles", self.min_size),
+                evaluation.interior.len() >= self.min_size,
+            ));
+        }
+        if let Some(max_size) = self.max_size {
+            checks.push((
+                format!("At most {} tiles", max_size),
+                evaluation.interior.len() <= max_size,
+            ));
+        }
+        if self.min_doors > 1 {
+            checks.push((
+                format!("At least {} doors", self.min_doors),
+                evaluation.doors.len() >= self.min_doors,
+            ));
+        }
+        checks
+    }
+}
+
 pub struct BlueprintPlugin;
 
 impl Plugin for BlueprintPlugin {
@@ -18,6 +62,7 @@ impl Plugin for BlueprintPlugin {
             .add_systems(
                 Update,
                 (
+                    evaluate_blueprints_system,
                     find_active_blueprint_system,
                     process_blueprint_system,
                     show_blueprint_ui_system,
@@ -112,18 +157,22 @@ pub fn setup_blueprints_system(mut commands: Commands, asset_server: Res<AssetSe
     });
     commands.insert_resource(ActiveBlueprint {
         active_blueprint: None,
+        region: Vec::new(),
     });
 }
 
 #[derive(Resource)]
 pub struct ActiveBlueprint {
     pub active_blueprint: Option<BlueprintInfo>,
+    /// The cells of the active blueprint's room and its walls, for the camera to frame. Empty while
+    /// the room leaks, since the flood fill then spreads far beyond it.
+    pub region: Vec<IVec2>,
 }
 
 #[derive(PartialEq, Eq)]
 pub struct BlueprintInfo {
-    blueprint_location: IVec2,
-    blueprint_entity: Entity,
+    pub blueprint_location: IVec2,
+    pub blueprint_entity: Entity,
 }
 
 pub fn find_active_blueprint_system(
@@ -156,61 +205,62 @@ pub fn find_active_blueprint_system(
     }
 }
 
-pub fn process_blueprint_system(
-    active_blueprint: Res<ActiveBlueprint>,
-    q_water: Query<(&Transform, &Water)>,
-    q_wall: Query<(&Transform, &Wall)>,
-    q_door: Query<(&Transform, &Door)>,
-    mut gizmos: Gizmos,
-) {
-    let Some(active_blueprint) = active_blueprint.active_blueprint.as_ref() else {
-        return;
-    };
+/// What occupies a cell, as far as a blueprint's flood fill is concerned.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum BlueprintCell {
+    Wall,
+    Water,
+    Door,
+}
 
-    #[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
-    enum GridType {
-        Wall,
-        Water,
-        Door,
-    }
+pub type BlueprintGrid = HashMap<IVec2, BlueprintCell>;
+
+/// The result of flood filling from a blueprint.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BlueprintEvaluation {
+    /// Every cell the flood fill reached, including the walls and doors bounding it.
+    pub region: Vec<IVec2>,
+    /// When the region is open, the path from the blueprint to where it leaked.
+    pub leak_path: Vec<IVec2>,
+    /// The floor cells of the room.
+    pub interior: Vec<IVec2>,
+    pub doors: Vec<IVec2>,
+    pub has_door: bool,
+    pub bounded_by_water: bool,
+    pub too_big: bool,
+}
 
-    let mut grid: HashMap<IVec2, GridType> = HashMap::new();
-    fn round(t: &Transform) -> IVec2 {
-        t.translation.xz().round().as_ivec2()
-    }
-    for (t, _water) in q_water.iter() {
-        grid.insert(round(&t), GridType::Water);
-    }
-    for (t, wall) in q_wall.iter() {
-        if wall.enabled {
-            grid.insert(round(&t), GridType::Wall);
-        }
-    }
-    for (t, _door) in q_door.iter() {
-        grid.insert(round(&t), GridType::Door);
+impl BlueprintEvaluation {
+    pub fn is_valid(&self) -> bool {
+        self.has_door && !self.bounded_by_water && !self.too_big
     }
+}
 
+/// The most cells a blueprint's region can reach before it is considered open.
+pub const MAX_REGION_SIZE: usize = 300;
+
+/// Flood fills from the blueprint at `location` until it is bounded by walls and doors, or leaks.
+pub fn evaluate_blueprint(grid: &BlueprintGrid, location: IVec2) -> BlueprintEvaluation {
     let mut reachable_queue: VecDeque<IVec2> = VecDeque::new();
     let mut reachable_from: HashMap<IVec2, IVec2> = HashMap::new();
 
-    reachable_from.insert(
-        active_blueprint.blueprint_location,
-        active_blueprint.blueprint_location,
-    );
-    reachable_queue.push_back(active_blueprint.blueprint_location);
+    reachable_from.insert(location, location);
+    reachable_queue.push_back(location);
 
     let mut bounded_by_water = false;
-    let mut has_door = false;
+    let mut doors: HashSet<IVec2> = HashSet::new();
     let mut too_big = false;
+    let mut interior: Vec<IVec2> = Vec::new();
 
     let mut bad_pos: Option<IVec2> = None;
 
     while let Some(current) = reachable_queue.pop_front() {
-        if reachable_from.len() > 300 {
+        if reachable_from.len() > MAX_REGION_SIZE {
             bad_pos = Some(current);
             too_big = true;
             break;
         }
+        interior.push(current);
         for dir in [IVec2::X, IVec2::Y, IVec2::NEG_X, IVec2::NEG_Y] {
             let neighbor = current + dir;
             if reachable_from.contains_key(&neighbor) {
@@ -218,17 +268,17 @@ pub fn process_blueprint_system(
             }
             reachable_from.insert(neighbor, current);
             let neighbor_cell = grid.get(&neighbor).copied();
-            if neighbor_cell == Some(GridType::Water) {
+            if neighbor_cell == Some(BlueprintCell::Water) {
                 bad_pos = Some(neighbor);
                 bounded_by_water = true;
                 break;
             }
-            if neighbor_cell == Some(GridType::Door) {
+            if neighbor_cell == Some(BlueprintCell::Door) {
                 // TODO: make sure it goes outside
-                has_door = true;
+                doors.insert(neighbor);
                 continue;
             }
-            if neighbor_cell == Some(GridType::Wall) {
+            if neighbor_cell == Some(BlueprintCell::Wall) {
                 continue;
             }
 
@@ -240,28 +290,213 @@ pub fn process_blueprint_system(
         }
     }
 
-    let good = has_door && !bounded_by_water && !too_big;
-
-    let mut bad_path: Vec<IVec2> = Vec::new();
+    let mut leak_path: Vec<IVec2> = Vec::new();
     while let Some(path_pos) = bad_pos {
-        if path_pos == active_blueprint.blueprint_location {
+        if path_pos == location {
             break;
         }
-        bad_path.push(path_pos);
+        leak_path.push(path_pos);
         bad_pos = reachable_from.get(&path_pos).copied();
     }
 
-    if good {
-        let color = Color::linear_rgb(0., 0., 1.);
-        for p in reachable_from.keys() {
-            let p = Vec3::new(p.x as f32, 0., p.y as f32);
-            gizmos.line(p, p + Vec3::Y * 6., color);
+    BlueprintEvaluation {
+        region: reachable_from.keys().copied().collect(),
+        leak_path,
+        interior,
+        has_door: !doors.is_empty(),
+        doors: doors.into_iter().collect(),
+        bounded_by_water,
+        too_big,
+    }
+}
+
+/// The latest evaluation of a blueprint, only changed when the evaluation is different.
+#[derive(Component, Default, PartialEq)]
+pub struct BlueprintStatus {
+    pub evaluation: BlueprintEvaluation,
+    /// The blueprint's requirements, if it has any, and whether each is met.
+    pub requirements: Vec<(String, bool)>,
+}
+
+impl BlueprintStatus {
+    /// Whether the room is closed, and meets all of the blueprint's requirements.
+    pub fn is_valid(&self) -> bool {
+        self.evaluation.is_valid() && self.requirements.iter().all(|(_, met)| *met)
+    }
+}
+
+pub fn evaluate_blueprints_system(
+    mut commands: Commands,
+    ground: Res<GroundGrid>,
+    q_wall: Query<(&Transform, &Wall)>,
+    q_door: Query<&Transform, With<Door>>,
+    mut blueprints: Query<
+        (
+            Entity,
+            &Transform,
+            Option<&BlueprintRequirements>,
+            Option<&mut BlueprintStatus>,
+        ),
+        With<Blueprint>,
+    >,
+) {
+    let mut grid: BlueprintGrid = HashMap::new();
+    fn round(t: &Transform) -> IVec2 {
+        t.translation.xz().round().as_ivec2()
+    }
+    for water in ground.water_cells() {
+        grid.insert(water, BlueprintCell::Water);
+    }
+    for (t, wall) in q_wall.iter() {
+        if wall.enabled {
+            grid.insert(round(t), BlueprintCell::Wall);
         }
+    }
+    for t in q_door.iter() {
+        grid.insert(round(t), BlueprintCell::Door);
+    }
+
+    for (blueprint_entity, blueprint_transform, requirements, status) in blueprints.iter_mut() {
+        let evaluation = evaluate_blueprint(&grid, round(blueprint_transform));
+        let requirements = requirements
+            .map(|requirements| requirements.check(&evaluation))
+            .unwrap_or_default();
+        let new_status = BlueprintStatus {
+            evaluation,
+            requirements,
+        };
+        match status {
+            // Only mark the status changed when it is, for `Changed<BlueprintStatus>` to be useful.
+            Some(mut status) => {
+                status.set_if_neq(new_status);
+            }
+            None => {
+                commands.entity(blueprint_entity).insert(new_status);
+            }
+        }
+    }
+}
+
+/// How a blueprint's room is marked out in the world.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default, Serialize, Deserialize)]
+pub enum FeedbackMode {
+    /// Tall lines over the room, or over the way out of it.
+    Lines,
+    /// Circles on the room's cells, and crosses on the way out of it.
+    Shapes,
+    /// A translucent overlay on the room's floor, drawn by the overlay module.
+    #[default]
+    Floor,
+}
+
+impl FeedbackMode {
+    pub const ALL: [FeedbackMode; 3] = [
+        FeedbackMode::Lines,
+        FeedbackMode::Shapes,
+        FeedbackMode::Floor,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            FeedbackMode::Lines => "Lines",
+            FeedbackMode::Shapes => "Shapes",
+            FeedbackMode::Floor => "Floor",
+        }
+    }
+}
+
+/// The colours for valid and invalid rooms.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default, Serialize, Deserialize)]
+pub enum Palette {
+    #[default]
+    Standard,
+    Deuteranopia,
+    Protanopia,
+    HighContrast,
+}
+
+impl Palette {
+    pub const ALL: [Palette; 4] = [
+        Palette::Standard,
+        Palette::Deuteranopia,
+        Palette::Protanopia,
+        Palette::HighContrast,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Palette::Standard => "Standard",
+            Palette::Deuteranopia => "Deuteranopia",
+            Palette::Protanopia => "Protanopia",
+            Palette::HighContrast => "High contrast",
+        }
+    }
+
+    pub fn valid(self) -> Color {
+        match self {
+            Palette::Standard => Color::linear_rgb(0., 0., 1.),
+            Palette::Deuteranopia => Color::srgb(0., 0.45, 0.7),
+            Palette::Protanopia => Color::srgb(0.35, 0.7, 0.9),
+            Palette::HighContrast => Color::linear_rgb(1., 1., 1.),
+        }
+    }
+
+    pub fn invalid(self) -> Color {
+        match self {
+            Palette::Standard => Color::linear_rgb(1., 0., 0.),
+            Palette::Deuteranopia => Color::srgb(0.9, 0.6, 0.),
+            Palette::Protanopia => Color::srgb(0.95, 0.9, 0.25),
+            Palette::HighContrast => Color::linear_rgb(1., 0., 1.),
+        }
+    }
+}
+
+pub fn process_blueprint_system(
+    mut active_blueprint: ResMut<ActiveBlueprint>,
+    statuses: Query<&BlueprintStatus>,
+    settings: Res<Settings>,
+    mut gizmos: Gizmos,
+) {
+    let ActiveBlueprint {
+        active_blueprint,
+        region,
+    } = &mut *active_blueprint;
+    region.clear();
+    let Some(active_blueprint) = active_blueprint.as_ref() else {
+        return;
+    };
+    let Ok(status) = statuses.get(active_blueprint.blueprint_entity) else {
+        return;
+    };
+    let evaluation = &status.evaluation;
+    if !evaluation.too_big && !evaluation.bounded_by_water {
+        region.extend(evaluation.region.iter().copied());
+    }
+
+    let is_valid = status.is_valid();
+    let (cells, color) = if is_valid {
+        (&evaluation.region, settings.palette.valid())
     } else {
-        let color = Color::linear_rgb(1., 0., 0.);
-        for p in &bad_path {
-            let p = Vec3::new(p.x as f32, 0., p.y as f32);
-            gizmos.line(p, p + Vec3::Y * 6., color);
+        (&evaluation.leak_path, settings.palette.invalid())
+    };
+    // Gizmo circles are drawn upright, so lay them down on the floor.
+    let flat = Quat::from_rotation_x(std::f32::consts::FRAC_PI_2);
+    for p in cells {
+        let p = Vec3::new(p.x as f32, 0.05, p.y as f32);
+        match settings.blueprint_feedback {
+            FeedbackMode::Lines => {
+                gizmos.line(p, p + Vec3::Y * 6., color);
+            }
+            FeedbackMode::Shapes if is_valid => {
+                gizmos.circle(Isometry3d::new(p, flat), 0.3, color);
+            }
+            FeedbackMode::Shapes => {
+                let (a, b) = (Vec3::new(0.3, 0., 0.3), Vec3::new(0.3, 0., -0.3));
+                gizmos.line(p - a, p + a, color);
+                gizmos.line(p - b, p + b, color);
+            }
+            // The overlay module draws this one.
+            FeedbackMode::Floor => {}
         }
     }
 }
@@ -269,13 +504,17 @@ pub fn process_blueprint_system(
 pub fn show_blueprint_ui_system(
     time: Res<Time>,
     active_blueprint: Res<ActiveBlueprint>,
+    statuses: Query<&BlueprintStatus>,
+    settings: Res<Settings>,
     ui: Res<BlueprintUi>,
     mut nodes: Query<&mut Node>,
+    mut texts: Query<(&mut Text, &mut TextColor)>,
 ) {
     let dt = time.delta_secs();
-    // let Some(active_blueprint) = active_blueprint.active_blueprint.as_ref() else {
-    //     return;
-    // };
+    let status = active_blueprint
+        .active_blueprint
+        .as_ref()
+        .and_then(|active_blueprint| statuses.get(active_blueprint.blueprint_entity).ok());
     let is_visible = active_blueprint.active_blueprint.is_some();
 
     let target_margin = if is_visible { 10. } else { -800. };
@@ -286,4 +525,74 @@ pub fn show_blueprint_ui_system(
         Val::Px(value) => Val::Px(value.lerp(target_margin, (10. * dt).min(1.))),
         _ => Val::Px(target_margin),
     };
+
+    // A checklist of what the room needs.
+    let Some(status) = status else {
+        return;
+    };
+    let evaluation = &status.evaluation;
+    let mut checks = vec![
+        (
+            "Not open to the water".to_string(),
+            !evaluation.bounded_by_water,
+        ),
+        ("Not too big".to_string(), !evaluation.too_big),
+        ("Has a door".to_string(), evaluation.has_door),
+    ];
+    checks.extend(status.requirements.iter().cloned());
+
+    for (index, line) in ui.lines.iter().enumerate() {
+        let check = checks.get(index);
+        if let Ok(mut line_node) = nodes.get_mut(line.container_entity) {
+            line_node.display = if check.is_some() {
+                Display::Flex
+            } else {
+                Display::None
+            };
+        }
+        let Some((description, met)) = check else {
+            continue;
+        };
+        if let Ok((mut text, mut text_color)) = texts.get_mut(line.text_entity) {
+            let mark = if *met { "[x]" } else { "[ ]" };
+            text.0 = format!("{} {}", mark, description);
+            text_color.0 = if *met {
+                Color::linear_rgb(1., 1., 1.)
+            } else {
+                Color::linear_rgb(1., 1., 1.).mix(&settings.palette.invalid(), 0.5)
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn closed_room_counts_its_floor() {
+        // A 2x2 room with a door in its top wall.
+        let mut grid = BlueprintGrid::new();
+        for x in -1..=2 {
+            for y in -1..=2 {
+                if x == -1 || x == 2 || y == -1 || y == 2 {
+                    grid.insert(IVec2::new(x, y), BlueprintCell::Wall);
+                }
+            }
+        }
+        grid.insert(IVec2::new(0, -1), BlueprintCell::Door);
+
+        let evaluation = evaluate_blueprint(&grid, IVec2::ZERO);
+        assert!(evaluation.is_valid());
+        assert_eq!(evaluation.interior.len(), 4);
+        assert_eq!(evaluation.doors, vec![IVec2::new(0, -1)]);
+    }
+
+    #[test]
+    fn open_room_does_not_count_where_it_stopped() {
+        let evaluation = evaluate_blueprint(&BlueprintGrid::new(), IVec2::ZERO);
+        assert!(evaluation.too_big);
+        let stopped_at = evaluation.leak_path[0];
+        assert!(!evaluation.interior.contains(&stopped_at));
+    }
 }
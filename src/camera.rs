@@ -0,0 +1,272 @@
+use bevy::{input::mouse::AccumulatedMouseScroll, platform::collections::HashMap, prelude::*};
+
+use crate::{
+    billboard::BillboardCamera,
+    blueprint::ActiveBlueprint,
+    controls::{Action, ActionState, game_running},
+    level::LevelBounds,
+    player::{Player, water_system},
+};
+
+pub struct CameraRigPlugin;
+
+impl Plugin for CameraRigPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CameraRig>()
+            .init_resource::<FadedMaterials>()
+            .add_systems(
+                Update,
+                (
+                    camera_input_system.run_if(game_running),
+                    fade_occluders_system,
+                ),
+            )
+            .add_systems(FixedUpdate, move_camera_system.after(water_system));
+    }
+}
+
+/// The direction from the player to the camera, before orbiting.
+const CAMERA_DIRECTION: Vec3 = Vec3::new(0., 8.5, 9.5);
+const MIN_DISTANCE: f32 = 6.;
+const MAX_DISTANCE: f32 = 22.;
+/// The player can move this far from the point the camera is looking at before it follows.
+const FOLLOW_DEAD_ZONE: f32 = 0.75;
+/// How far the camera can pull back to fit a blueprint's region in view.
+const MAX_FRAMING_DISTANCE: f32 = 30.;
+
+/// Where the camera wants to be. The camera transform eases towards this each step.
+#[derive(Resource)]
+pub struct CameraRig {
+    /// How many quarter turns the camera has orbited around the player.
+    pub quarter_turns: i32,
+    /// The current orbit angle, which eases towards `quarter_turns`.
+    pub yaw: f32,
+    /// How far the camera is from the point it is looking at.
+    pub distance: f32,
+    pub target_distance: f32,
+    /// The point the camera is looking at.
+    pub focus: Option<Vec3>,
+    /// When set, the camera pulls back to show the whole room of the active blueprint, once it is
+    /// closed off.
+    pub frame_blueprints: bool,
+}
+
+impl Default for CameraRig {
+    fn default() -> Self {
+        Self {
+            quarter_turns: 0,
+            yaw: 0.,
+            distance: CAMERA_DIRECTION.length(),
+            target_distance: CAMERA_DIRECTION.length(),
+            focus: None,
+            frame_blueprints: true,
+        }
+    }
+}
+
+impl CameraRig {
+    fn target_yaw(&self) -> f32 {
+        self.quarter_turns as f32 * std::f32::consts::FRAC_PI_2
+    }
+
+    /// The flat forward direction for movement. This uses the orbit the camera is turning towards,
+    /// so that the controls change as soon as an orbit starts.
+    pub fn forward(&self) -> Vec3 {
+        Quat::from_rotation_y(self.target_yaw()) * Vec3::NEG_Z
+    }
+
+    pub fn right(&self) -> Vec3 {
+        Quat::from_rotation_y(self.target_yaw()) * Vec3::X
+    }
+}
+
+pub fn camera_input_system(
+    action_state: Res<ActionState>,
+    scroll: Res<AccumulatedMouseScroll>,
+    mut rig: ResMut<CameraRig>,
+) {
+    if action_state.just_pressed(Action::OrbitLeft) {
+        rig.quarter_turns -= 1;
+    }
+    if action_state.just_pressed(Action::OrbitRight) {
+        rig.quarter_turns += 1;
+    }
+    if scroll.delta.y != 0. {
+        rig.target_distance =
+            (rig.target_distance * 0.9f32.powf(scroll.delta.y)).clamp(MIN_DISTANCE, MAX_DISTANCE);
+    }
+}
+
+/// The half width and half depth of the ground the camera can see, around the point it looks at.
+/// This ignores the tilt of the camera, which is close enough to keep the edges of the level in view.
+fn view_half_extents(projection: &Projection, distance: f32, yaw: f32) -> Vec2 {
+    let (fov, aspect_ratio) = match projection {
+        Projection::Perspective(perspective) => (perspective.fov, perspective.aspect_ratio),
+        _ => (std::f32::consts::FRAC_PI_4, 16. / 9.),
+    };
+    let half_height = distance * (fov / 2.).tan();
+    let half_extents = Vec2::new(half_height * aspect_ratio, half_height);
+    // After an odd number of quarter turns, the screen's width runs along z instead of x.
+    let turned = Vec2::from_angle(yaw);
+    Vec2::new(
+        (half_extents.x * turned.x).abs() + (half_extents.y * turned.y).abs(),
+        (half_extents.x * turned.y).abs() + (half_extents.y * turned.x).abs(),
+    )
+}
+
+/// Moves `focus` so that as little as possible outside of `bounds` is in view. When the level is
+/// smaller than the view along an axis, the level is centered instead.
+fn confine_focus(focus: Vec3, half_extents: Vec2, bounds: &LevelBounds) -> Vec3 {
+    let confine = |value: f32, half_extent: f32, min: f32, max: f32| {
+        if max - min <= half_extent * 2. {
+            (min + max) / 2.
+        } else {
+            value.clamp(min + half_extent, max - half_extent)
+        }
+    };
+    Vec3::new(
+        confine(focus.x, half_extents.x, bounds.min.x, bounds.max.x),
+        focus.y,
+        confine(focus.z, half_extents.y, bounds.min.y, bounds.max.y),
+    )
+}
+
+pub fn move_camera_system(
+    time: Res<Time>,
+    mut rig: ResMut<CameraRig>,
+    bounds: Option<Res<LevelBounds>>,
+    active_blueprint: Option<Res<ActiveBlueprint>>,
+    mut camera: Query<(&mut Transform, &Projection), With<BillboardCamera>>,
+    player: Query<&Transform, (With<Player>, Without<BillboardCamera>)>,
+) {
+    let Ok(player) = player.single() else {
+        return;
+    };
+    let Ok((mut camera, projection)) = camera.single_mut() else {
+        return;
+    };
+    let dt = time.delta_secs();
+
+    let blueprint_region = active_blueprint
+        .as_ref()
+        .filter(|active_blueprint| rig.frame_blueprints && !active_blueprint.region.is_empty())
+        .map(|active_blueprint| {
+            let cells = active_blueprint.region.iter().map(|cell| cell.as_vec2());
+            let min = cells.clone().fold(Vec2::MAX, Vec2::min) - 0.5;
+            let max = cells.fold(Vec2::MIN, Vec2::max) + 0.5;
+            (min, max)
+        });
+
+    let yaw = rig.yaw.lerp(rig.target_yaw(), (dt * 10.).min(1.));
+    let (focus, target_distance) = match (rig.focus, blueprint_region) {
+        // Snap to the player the first time, rather than sweeping in from the origin.
+        (None, _) => (player.translation, rig.target_distance),
+        (Some(focus), Some((min, max))) => {
+            let center = (min + max) / 2.;
+            let center = Vec3::new(center.x, player.translation.y, center.y);
+            // Pull back until the region fits in view, with a cell of margin.
+            let unit_extents = view_half_extents(projection, 1., yaw);
+            let needed = ((max - min) / 2. + 1.) / unit_extents;
+            let framing_distance = needed.max_element().min(MAX_FRAMING_DISTANCE);
+            (
+                focus.lerp(center, (dt * 4.).min(1.)),
+                rig.target_distance.max(framing_distance),
+            )
+        }
+        (Some(focus), None) => {
+            let offset = (player.translation - focus).clamp_length_max(FOLLOW_DEAD_ZONE);
+            (
+                focus.lerp(player.translation - offset, (dt * 8.).min(1.)),
+                rig.target_distance,
+            )
+        }
+    };
+    let distance = rig.distance.lerp(target_distance, (dt * 10.).min(1.));
+    let focus = match bounds {
+        Some(bounds) => confine_focus(focus, view_half_extents(projection, distance, yaw), &bounds),
+        None => focus,
+    };
+    rig.focus = Some(focus);
+    rig.yaw = yaw;
+    rig.distance = distance;
+
+    let direction = Quat::from_rotation_y(yaw) * CAMERA_DIRECTION.normalize();
+    *camera = Transform::from_translation(focus + direction * distance).looking_at(focus, Vec3::Y);
+}
+
+/// Meshes with this component turn see-through when they are between the camera and the player.
+#[derive(Component)]
+pub struct FadeWhenOccluding;
+
+/// Remembers the material of a faded mesh, so it can be restored.
+#[derive(Component)]
+pub struct Faded {
+    pub solid: Handle<StandardMaterial>,
+}
+
+/// A see-through copy of each material that has been faded.
+#[derive(Resource, Default)]
+pub struct FadedMaterials {
+    pub faded: HashMap<AssetId<StandardMaterial>, Handle<StandardMaterial>>,
+}
+
+/// Whether a wall in `cell` would hide the player from the camera. The camera looks down steeply,
+/// so only walls just in front of the player (towards the camera) get in the way.
+fn is_occluding(cell: Vec2, player: Vec2, to_camera: Vec2) -> bool {
+    let offset = cell - player;
+    let along = offset.dot(to_camera);
+    let across = offset.perp_dot(to_camera).abs();
+    along > 0. && along < 2.5 && across < 0.9
+}
+
+pub fn fade_occluders_system(
+    mut commands: Commands,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut faded_materials: ResMut<FadedMaterials>,
+    camera: Query<&Transform, With<BillboardCamera>>,
+    player: Query<&Transform, With<Player>>,
+    mut occluders: Query<
+        (
+            Entity,
+            &Transform,
+            &mut MeshMaterial3d<StandardMaterial>,
+            Option<&Faded>,
+        ),
+        With<FadeWhenOccluding>,
+    >,
+) {
+    let Ok(camera) = camera.single() else {
+        return;
+    };
+    let Ok(player) = player.single() else {
+        return;
+    };
+    let player_position = player.translation.xz();
+    let to_camera = (camera.translation.xz() - player_position).normalize_or_zero();
+
+    for (entity, transform, mut material, faded) in occluders.iter_mut() {
+        let occluding = is_occluding(transform.translation.xz(), player_position, to_camera);
+        match (occluding, faded) {
+            (true, None) => {
+                let solid = material.0.clone();
+                let faded_material = faded_materials
+                    .faded
+                    .entry(solid.id())
+                    .or_insert_with(|| {
+                        let mut faded_material = materials.get(&solid).cloned().unwrap_or_default();
+                        faded_material.base_color.set_alpha(0.3);
+                        faded_material.alpha_mode = AlphaMode::Blend;
+                        materials.add(faded_material)
+                    })
+                    .clone();
+                material.0 = faded_material;
+                commands.entity(entity).insert(Faded { solid });
+            }
+            (false, Some(faded)) => {
+                material.0 = faded.solid.clone();
+                commands.entity(entity).remove::<Faded>();
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,249 @@
+use std::collections::BTreeMap;
+
+use bevy::{input::InputSystems, platform::collections::HashSet, prelude::*};
+use serde::{Deserialize, Serialize};
+
+pub struct ControlsPlugin;
+
+impl Plugin for ControlsPlugin {
+    fn build(&self, app: &mut App) {
+        // The bindings are loaded and saved with the rest of the settings, see `SettingsPlugin`.
+        app.init_resource::<ActionMap>()
+            .init_resource::<ActionState>()
+            .add_systems(PreUpdate, update_action_state_system.after(InputSystems))
+            .add_systems(Update, pause_system);
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Serialize, Deserialize)]
+pub enum Action {
+    MoveUp,
+    MoveDown,
+    MoveLeft,
+    MoveRight,
+    /// Pick up or place items.
+    Interact,
+    /// Turn the held items a quarter turn.
+    Rotate,
+    /// Put the held items back where they were picked up.
+    Undo,
+    /// Turn the camera a quarter turn around the player.
+    OrbitLeft,
+    OrbitRight,
+    Pause,
+    /// Point out something the active blueprint's room is missing.
+    Hint,
+}
+
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub enum Binding {
+    Key(KeyCode),
+    Gamepad(GamepadButton),
+    Mouse(MouseButton),
+}
+
+#[derive(Resource, Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ActionMap {
+    pub bindings: BTreeMap<Action, Vec<Binding>>,
+    /// The left stick is ignored when it is pushed less than this far.
+    pub stick_dead_zone: f32,
+    /// When enabled, the cell under the mouse pointer is targeted for pickup and placement, instead
+    /// of the cell the player is walking towards, and clicking interacts with it.
+    pub mouse_targeting: bool,
+}
+
+impl Default for ActionMap {
+    fn default() -> Self {
+        use Binding::{Gamepad, Key};
+        let bindings = [
+            (
+                Action::MoveUp,
+                vec![
+                    Key(KeyCode::KeyW),
+                    Key(KeyCode::ArrowUp),
+                    Gamepad(GamepadButton::DPadUp),
+                ],
+            ),
+            (
+                Action::MoveDown,
+                vec![
+                    Key(KeyCode::KeyS),
+                    Key(KeyCode::ArrowDown),
+                    Gamepad(GamepadButton::DPadDown),
+                ],
+            ),
+            (
+                Action::MoveLeft,
+                vec![
+                    Key(KeyCode::KeyA),
+                    Key(KeyCode::ArrowLeft),
+                    Gamepad(GamepadButton::DPadLeft),
+                ],
+            ),
+            (
+                Action::MoveRight,
+                vec![
+                    Key(KeyCode::KeyD),
+                    Key(KeyCode::ArrowRight),
+                    Gamepad(GamepadButton::DPadRight),
+                ],
+            ),
+            (
+                Action::Interact,
+                vec![Key(KeyCode::KeyE), Gamepad(GamepadButton::South)],
+            ),
+            (
+                Action::Rotate,
+                vec![Key(KeyCode::KeyR), Gamepad(GamepadButton::West)],
+            ),
+            (
+                Action::Undo,
+                vec![Key(KeyCode::KeyZ), Gamepad(GamepadButton::North)],
+            ),
+            (
+                Action::OrbitLeft,
+                vec![Key(KeyCode::KeyQ), Gamepad(GamepadButton::LeftTrigger)],
+            ),
+            (
+                Action::OrbitRight,
+                vec![Key(KeyCode::KeyF), Gamepad(GamepadButton::RightTrigger)],
+            ),
+            (
+                Action::Pause,
+                vec![Key(KeyCode::Escape), Gamepad(GamepadButton::Start)],
+            ),
+            (
+                Action::Hint,
+                vec![Key(KeyCode::KeyH), Gamepad(GamepadButton::Select)],
+            ),
+        ];
+        Self {
+            bindings: bindings.into_iter().collect(),
+            stick_dead_zone: 0.2,
+            mouse_targeting: false,
+        }
+    }
+}
+
+/// Added to the bindings while [`ActionMap::mouse_targeting`] is enabled.
+const MOUSE_TARGETING_BINDING: (Action, Binding) =
+    (Action::Interact, Binding::Mouse(MouseButton::Left));
+
+impl ActionMap {
+    /// Every binding in use, including the click to interact while mouse targeting is enabled.
+    pub fn active_bindings(&self) -> impl Iterator<Item = (Action, &Binding)> {
+        let mouse_targeting = self
+            .mouse_targeting
+            .then_some((MOUSE_TARGETING_BINDING.0, &MOUSE_TARGETING_BINDING.1));
+        self.bindings
+            .iter()
+            .flat_map(|(&action, bindings)| bindings.iter().map(move |binding| (action, binding)))
+            .chain(mouse_targeting)
+    }
+
+    /// Gives actions added since these bindings were saved their default bindings.
+    pub fn fill_in_missing_bindings(&mut self) {
+        for (action, bindings) in ActionMap::default().bindings {
+            self.bindings.entry(action).or_insert(bindings);
+        }
+    }
+}
+
+/// The state of every action this frame, combining all of their bindings.
+#[derive(Resource, Default)]
+pub struct ActionState {
+    /// Movement intent, with x to the right and y forward. Each component is in -1..=1.
+    pub move_axis: Vec2,
+    pressed: HashSet<Action>,
+    just_pressed: HashSet<Action>,
+}
+
+impl ActionState {
+    pub fn pressed(&self, action: Action) -> bool {
+        self.pressed.contains(&action)
+    }
+
+    pub fn just_pressed(&self, action: Action) -> bool {
+        self.just_pressed.contains(&action)
+    }
+
+    /// Hides this frame's input from gameplay, for when a menu or dialogue has used it.
+    pub fn clear(&mut self) {
+        self.move_axis = Vec2::ZERO;
+        self.pressed.clear();
+        self.just_pressed.clear();
+    }
+
+    /// Hides one action from gameplay this frame, leaving the rest of the input alone.
+    pub fn release(&mut self, action: Action) {
+        self.pressed.remove(&action);
+        self.just_pressed.remove(&action);
+    }
+}
+
+pub fn update_action_state_system(
+    action_map: Res<ActionMap>,
+    keys: Res<ButtonInput<KeyCode>>,
+    mouse_buttons: Res<ButtonInput<MouseButton>>,
+    gamepads: Query<&Gamepad>,
+    mut action_state: ResMut<ActionState>,
+) {
+    action_state.pressed.clear();
+    action_state.just_pressed.clear();
+
+    for (action, binding) in action_map.active_bindings() {
+        let (pressed, just_pressed) = match binding {
+            Binding::Key(key) => (keys.pressed(*key), keys.just_pressed(*key)),
+            Binding::Gamepad(button) => (
+                gamepads.iter().any(|gamepad| gamepad.pressed(*button)),
+                gamepads.iter().any(|gamepad| gamepad.just_pressed(*button)),
+            ),
+            Binding::Mouse(button) => (
+                mouse_buttons.pressed(*button),
+                mouse_buttons.just_pressed(*button),
+            ),
+        };
+        if pressed {
+            action_state.pressed.insert(action);
+        }
+        if just_pressed {
+            action_state.just_pressed.insert(action);
+        }
+    }
+
+    let mut move_axis = Vec2::ZERO;
+    for (action, direction) in [
+        (Action::MoveUp, Vec2::Y),
+        (Action::MoveDown, Vec2::NEG_Y),
+        (Action::MoveLeft, Vec2::NEG_X),
+        (Action::MoveRight, Vec2::X),
+    ] {
+        if action_state.pressed(action) {
+            move_axis += direction;
+        }
+    }
+    for gamepad in gamepads.iter() {
+        let stick = gamepad.left_stick();
+        if stick.length() > action_map.stick_dead_zone {
+            move_axis += stick;
+        }
+    }
+    action_state.move_axis = move_axis.clamp(Vec2::NEG_ONE, Vec2::ONE);
+}
+
+/// Run condition for gameplay systems, which shouldn't react to input while the game is paused.
+pub fn game_running(time: Res<Time<Virtual>>) -> bool {
+    !time.is_paused()
+}
+
+pub fn pause_system(action_state: Res<ActionState>, mut time: ResMut<Time<Virtual>>) {
+    if !action_state.just_pressed(Action::Pause) {
+        return;
+    }
+    if time.is_paused() {
+        time.unpause();
+    } else {
+        time.pause();
+    }
+}
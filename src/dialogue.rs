@@ -0,0 +1,464 @@
+use std::collections::BTreeMap;
+
+use bevy::{platform::collections::HashSet, prelude::*};
+use serde::Deserialize;
+
+use crate::{
+    billboard::Billboard,
+    blueprint::{ActiveBlueprint, BlueprintStatus},
+    controls::{Action, ActionState, game_running, update_action_state_system},
+    item::Item,
+    player::Player,
+};
+
+pub struct DialoguePlugin;
+
+impl Plugin for DialoguePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<DialogueState>()
+            .add_systems(Startup, setup_dialogue_system)
+            .add_systems(
+                PreUpdate,
+                dialogue_input_system
+                    .after(update_action_state_system)
+                    .run_if(game_running),
+            )
+            .add_systems(Update, (show_talk_prompt_system, show_dialogue_ui_system));
+    }
+}
+
+/// How close the player's cursor must be to an NPC to talk to them.
+pub const TALK_RANGE: f32 = 0.8;
+
+/// A character the player can talk to.
+#[derive(Component)]
+pub struct Npc {
+    pub dialogue: Dialogue,
+}
+
+/// A conversation, loaded from a `.ron` file in `assets/dialogue`.
+#[derive(Deserialize, Debug)]
+pub struct Dialogue {
+    pub speaker: String,
+    /// The conversation starts at the first of these whose condition holds.
+    pub start: Vec<DialogueBranch>,
+    pub nodes: BTreeMap<String, DialogueNode>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct DialogueBranch {
+    #[serde(default)]
+    pub condition: Option<Condition>,
+    pub node: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct DialogueNode {
+    pub lines: Vec<String>,
+    /// Shown after the last line. With no (available) choices, the conversation moves on to `next`.
+    #[serde(default)]
+    pub choices: Vec<DialogueChoice>,
+    /// Where the conversation goes after the last line, or `None` to end it.
+    #[serde(default)]
+    pub next: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct DialogueChoice {
+    pub text: String,
+    #[serde(default)]
+    pub next: Option<String>,
+    /// The choice is only offered when this holds.
+    #[serde(default)]
+    pub condition: Option<Condition>,
+    /// Remembered for the rest of the game, for later conditions to check.
+    #[serde(default)]
+    pub set_flag: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+pub enum Condition {
+    /// The player is standing near a blueprint.
+    BlueprintActive,
+    AnyBlueprintValid,
+    AllBlueprintsValid,
+    HoldingItem,
+    /// A flag set by an earlier choice.
+    Flag(String),
+    Not(Box<Condition>),
+}
+
+/// The game state which dialogue conditions can check.
+pub struct DialogueContext<'a> {
+    pub blueprint_active: bool,
+    pub any_blueprint_valid: bool,
+    pub all_blueprints_valid: bool,
+    pub holding_item: bool,
+    pub flags: &'a HashSet<String>,
+}
+
+impl Condition {
+    pub fn holds(&self, context: &DialogueContext) -> bool {
+        match self {
+            Condition::BlueprintActive => context.blueprint_active,
+            Condition::AnyBlueprintValid => context.any_blueprint_valid,
+            Condition::AllBlueprintsValid => context.all_blueprints_valid,
+            Condition::HoldingItem => context.holding_item,
+            Condition::Flag(flag) => context.flags.contains(flag),
+            Condition::Not(condition) => !condition.holds(context),
+        }
+    }
+}
+
+impl<'a> DialogueContext<'a> {
+    pub fn new(
+        active_blueprint: Option<&ActiveBlueprint>,
+        blueprint_statuses: &Query<&BlueprintStatus>,
+        items: &Query<&Item>,
+        flags: &'a HashSet<String>,
+    ) -> DialogueContext<'a> {
+        DialogueContext {
+            blueprint_active: active_blueprint
+                .is_some_and(|active| active.active_blueprint.is_some()),
+            any_blueprint_valid: blueprint_statuses.iter().any(|status| status.is_valid()),
+            // A level with no blueprints has nothing to finish.
+            all_blueprints_valid: !blueprint_statuses.is_empty()
+                && blueprint_statuses.iter().all(|status| status.is_valid()),
+            holding_item: items.iter().any(|item| item.is_held.is_some()),
+            flags,
+        }
+    }
+}
+
+fn condition_holds(condition: &Option<Condition>, context: &DialogueContext) -> bool {
+    condition
+        .as_ref()
+        .is_none_or(|condition| condition.holds(context))
+}
+
+impl Dialogue {
+    pub fn load(path: &str) -> Result<Dialogue, Box<dyn std::error::Error>> {
+        let text = std::fs::read_to_string(path)?;
+        Ok(ron::from_str(&text)?)
+    }
+
+    pub fn start_node(&self, context: &DialogueContext) -> Option<&str> {
+        self.start
+            .iter()
+            .find(|branch| condition_holds(&branch.condition, context))
+            .map(|branch| branch.node.as_str())
+    }
+
+    /// The choices of `node` which are currently offered.
+    pub fn choices<'a>(
+        &'a self,
+        node: &'a DialogueNode,
+        context: &'a DialogueContext,
+    ) -> impl Iterator<Item = &'a DialogueChoice> + 'a {
+        node.choices
+            .iter()
+            .filter(move |choice| condition_holds(&choice.condition, context))
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct DialogueState {
+    /// The NPC the player can currently talk to.
+    pub nearby: Option<Entity>,
+    pub open: Option<OpenDialogue>,
+    pub flags: HashSet<String>,
+}
+
+pub struct OpenDialogue {
+    pub npc: Entity,
+    pub node: String,
+    /// The index of the line being shown.
+    pub line: usize,
+    /// The highlighted choice, once all of the lines have been shown.
+    pub choice: usize,
+}
+
+pub fn dialogue_input_system(
+    mut action_state: ResMut<ActionState>,
+    mut dialogue_state: ResMut<DialogueState>,
+    active_blueprint: Option<Res<ActiveBlueprint>>,
+    blueprint_statuses: Query<&BlueprintStatus>,
+    items: Query<&Item>,
+    player: Query<&Player>,
+    npcs: Query<(Entity, &Transform, &Npc)>,
+) {
+    let Ok(player) = player.single() else {
+        return;
+    };
+    let DialogueState {
+        nearby,
+        open,
+        flags,
+    } = &mut *dialogue_state;
+
+    let mut new_flags: Vec<String> = Vec::new();
+    let context = DialogueContext::new(
+        active_blueprint.as_deref(),
+        &blueprint_statuses,
+        &items,
+        flags,
+    );
+
+    *nearby = npcs
+        .iter()
+        .filter(|(_, npc_transform, _)| {
+            npc_transform.translation.xz().distance(player.cursor.xz()) < TALK_RANGE
+        })
+        .min_by(|(_, a, _), (_, b, _)| {
+            let a = a.translation.xz().distance(player.cursor.xz());
+            let b = b.translation.xz().distance(player.cursor.xz());
+            a.total_cmp(&b)
+        })
+        .map(|(npc_entity, _, _)| npc_entity);
+
+    match open {
+        None => {
+            let Some(npc_entity) = *nearby else {
+                return;
+            };
+            if context.holding_item || !action_state.just_pressed(Action::Interact) {
+                return;
+            }
+            let Ok((_, _, npc)) = npcs.get(npc_entity) else {
+                return;
+            };
+            if let Some(node) = npc.dialogue.start_node(&context) {
+                *open = Some(OpenDialogue {
+                    npc: npc_entity,
+                    node: node.to_string(),
+                    line: 0,
+                    choice: 0,
+                });
+            }
+        }
+        Some(conversation) => {
+            let node = npcs
+                .get(conversation.npc)
+                .ok()
+                .and_then(|(_, _, npc)| Some((npc, npc.dialogue.nodes.get(&conversation.node)?)));
+            let Some((npc, node)) = node else {
+                // The NPC or node is missing, so there is nothing to say.
+                *open = None;
+                return;
+            };
+
+            let mut next = None;
+            if conversation.line + 1 < node.lines.len() {
+                if action_state.just_pressed(Action::Interact) {
+                    conversation.line += 1;
+                }
+            } else {
+                let choices: Vec<&DialogueChoice> = npc.dialogue.choices(node, &context).collect();
+                if choices.is_empty() {
+                    if action_state.just_pressed(Action::Interact) {
+                        next = Some(node.next.clone());
+                    }
+                } else {
+                    if action_state.just_pressed(Action::MoveUp) {
+                        conversation.choice = conversation.choice.saturating_sub(1);
+                    }
+                    if action_state.just_pressed(Action::MoveDown) {
+                        conversation.choice = (conversation.choice + 1).min(choices.len() - 1);
+                    }
+                    conversation.choice = conversation.choice.min(choices.len() - 1);
+                    if action_state.just_pressed(Action::Interact) {
+                        let choice = choices[conversation.choice];
+                        new_flags.extend(choice.set_flag.clone());
+                        next = Some(choice.next.clone());
+                    }
+                }
+            }
+
+            match next {
+                None => {}
+                Some(Some(node)) => {
+                    conversation.node = node;
+                    conversation.line = 0;
+                    conversation.choice = 0;
+                }
+                Some(None) => *open = None,
+            }
+        }
+    }
+
+    flags.extend(new_flags);
+    // The player is talking, so gameplay shouldn't also react to this input.
+    action_state.clear();
+}
+
+#[derive(Component)]
+pub struct TalkIcon;
+
+#[derive(Component)]
+pub struct DialogueBox;
+
+#[derive(Component)]
+pub struct DialogueSpeakerText;
+
+#[derive(Component)]
+pub struct DialogueLineText;
+
+#[derive(Component)]
+pub struct DialogueChoicesText;
+
+pub fn setup_dialogue_system(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let font = asset_server.load("fonts/delius/Delius-Regular.ttf");
+    let text_font = |font_size: f32| TextFont {
+        font: font.clone(),
+        font_size,
+        ..default()
+    };
+
+    commands.spawn((
+        TalkIcon,
+        Billboard {
+            image: "talk_icon.png".to_string(),
+            ..default()
+        },
+        Transform::from_scale(Vec3::ZERO),
+    ));
+
+    commands
+        .spawn(Node {
+            position_type: PositionType::Absolute,
+            width: percent(100),
+            height: percent(100),
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::FlexEnd,
+            ..default()
+        })
+        .with_children(|builder| {
+            builder
+                .spawn((
+                    DialogueBox,
+                    Node {
+                        display: Display::None,
+                        flex_direction: FlexDirection::Column,
+                        width: px(800),
+                        margin: UiRect::bottom(px(30)),
+                        padding: UiRect::axes(px(20), px(15)),
+                        row_gap: px(8),
+                        ..default()
+                    },
+                    BackgroundColor(Color::linear_rgba(0.05, 0.05, 0.15, 0.8)),
+                ))
+                .with_children(|builder| {
+                    builder.spawn((
+                        DialogueSpeakerText,
+                        Text::default(),
+                        TextColor(Color::linear_rgb(1., 0.85, 0.4)),
+                        text_font(22.),
+                    ));
+                    builder.spawn((
+                        DialogueLineText,
+                        Text::default(),
+                        TextColor(Color::linear_rgb(1., 1., 1.)),
+                        text_font(25.),
+                    ));
+                    builder.spawn((
+                        DialogueChoicesText,
+                        Text::default(),
+                        TextColor(Color::linear_rgb(0.7, 0.8, 1.)),
+                        text_font(22.),
+                    ));
+                });
+        });
+}
+
+/// Shows the talk icon over the NPC the player can talk to, like the grab icon over items.
+pub fn show_talk_prompt_system(
+    dialogue_state: Res<DialogueState>,
+    mut icon: Query<&mut Transform, With<TalkIcon>>,
+    npcs: Query<&Transform, (With<Npc>, Without<TalkIcon>)>,
+) {
+    let Ok(mut icon_transform) = icon.single_mut() else {
+        return;
+    };
+    let npc_transform = dialogue_state
+        .nearby
+        .filter(|_| dialogue_state.open.is_none())
+        .and_then(|npc_entity| npcs.get(npc_entity).ok());
+    *icon_transform = match npc_transform {
+        Some(npc_transform) => {
+            Transform::from_translation(npc_transform.translation + Vec3::Y * 1.1)
+                .with_scale(Vec3::splat(0.5))
+        }
+        None => Transform::from_scale(Vec3::ZERO),
+    };
+}
+
+pub fn show_dialogue_ui_system(
+    dialogue_state: Res<DialogueState>,
+    active_blueprint: Option<Res<ActiveBlueprint>>,
+    blueprint_statuses: Query<&BlueprintStatus>,
+    items: Query<&Item>,
+    npcs: Query<&Npc>,
+    mut dialogue_box: Query<&mut Node, With<DialogueBox>>,
+    mut speaker_text: Query<&mut Text, With<DialogueSpeakerText>>,
+    mut line_text: Query<&mut Text, (With<DialogueLineText>, Without<DialogueSpeakerText>)>,
+    mut choices_text: Query<
+        &mut Text,
+        (
+            With<DialogueChoicesText>,
+            Without<DialogueSpeakerText>,
+            Without<DialogueLineText>,
+        ),
+    >,
+) {
+    let Ok(mut dialogue_box) = dialogue_box.single_mut() else {
+        return;
+    };
+    let conversation = dialogue_state.open.as_ref().and_then(|conversation| {
+        let npc = npcs.get(conversation.npc).ok()?;
+        let node = npc.dialogue.nodes.get(&conversation.node)?;
+        Some((conversation, npc, node))
+    });
+    let Some((conversation, npc, node)) = conversation else {
+        dialogue_box.display = Display::None;
+        return;
+    };
+    dialogue_box.display = Display::Flex;
+
+    let context = DialogueContext::new(
+        active_blueprint.as_deref(),
+        &blueprint_statuses,
+        &items,
+        &dialogue_state.flags,
+    );
+
+    if let Ok(mut text) = speaker_text.single_mut() {
+        text.0 = npc.dialogue.speaker.clone();
+    }
+    if let Ok(mut text) = line_text.single_mut() {
+        text.0 = node
+            .lines
+            .get(conversation.line)
+            .cloned()
+            .unwrap_or_default();
+    }
+    if let Ok(mut text) = choices_text.single_mut() {
+        let is_last_line = conversation.line + 1 >= node.lines.len();
+        text.0 = if is_last_line {
+            npc.dialogue
+                .choices(node, &context)
+                .enumerate()
+                .map(|(index, choice)| {
+                    let marker = if index == conversation.choice {
+                        ">"
+                    } else {
+                        " "
+                    };
+                    format!("{} {}", marker, choice.text)
+                })
+                .collect::<Vec<String>>()
+                .join("\n")
+        } else {
+            String::new()
+        };
+    }
+}
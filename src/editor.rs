@@ -0,0 +1,488 @@
+//! An in-game level editor, built with the `editor` feature.
+//!
+//! F2 starts editing, and F2 again test-plays the edited level from the start. While editing, the
+//! player can still walk around to look at the level, but the mouse paints tiles instead of
+//! picking up items. Ctrl+S saves the level as level text, which the game then loads instead of the
+//! level image.
+
+use bevy::{prelude::*, window::PrimaryWindow};
+
+use crate::{
+    billboard::BillboardCamera,
+    controls::{Action, ActionState, update_action_state_system},
+    ground::{GroundGrid, GroundTile},
+    item::PickupHistory,
+    jobs::{Client, Commission, Jobs},
+    level::{LEVEL_TEXT_PATH, Level, LevelTile, MAX_GLUE_GROUP},
+    level_spawn::{LevelAssets, LevelEntity, spawn_level, spawn_level_cell},
+    lint::{Diagnostic, Severity, lint_level},
+    player::Player,
+};
+
+pub struct EditorPlugin;
+
+impl Plugin for EditorPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Editor>()
+            .add_systems(Startup, setup_editor_system)
+            .add_systems(
+                PreUpdate,
+                release_gameplay_actions_system
+                    .after(update_action_state_system)
+                    .run_if(editing),
+            )
+            .add_systems(
+                Update,
+                (
+                    toggle_editor_system,
+                    (hover_cell_system, edit_level_system, draw_editor_system)
+                        .chain()
+                        .run_if(editing),
+                    show_editor_ui_system,
+                )
+                    .chain(),
+            );
+    }
+}
+
+/// The actions the mouse and keys would otherwise do in the level while editing.
+const GAMEPLAY_ACTIONS: [Action; 4] =
+    [Action::Interact, Action::Rotate, Action::Undo, Action::Hint];
+
+/// Each brush's key, in the order of [`LevelTile::ALL`].
+const BRUSH_KEYS: [KeyCode; 9] = [
+    KeyCode::Digit1,
+    KeyCode::Digit2,
+    KeyCode::Digit3,
+    KeyCode::Digit4,
+    KeyCode::Digit5,
+    KeyCode::Digit6,
+    KeyCode::Digit7,
+    KeyCode::Digit8,
+    KeyCode::Digit9,
+];
+
+#[derive(Resource, Default)]
+pub struct Editor {
+    pub editing: bool,
+    /// The index in [`LevelTile::ALL`] of the tile the left mouse button paints.
+    pub brush: usize,
+    /// The level cell under the mouse.
+    pub hovered: Option<IVec2>,
+    /// The glue group being built, while the glue tool is in use.
+    pub glue_group: Option<Vec<IVec2>>,
+    /// What happened last, like the level being saved.
+    pub status: String,
+}
+
+/// Run condition for the editing tools.
+pub fn editing(editor: Res<Editor>) -> bool {
+    editor.editing
+}
+
+#[derive(Component)]
+pub struct EditorPanel;
+
+#[derive(Component)]
+pub struct EditorText;
+
+pub fn setup_editor_system(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let font = asset_server.load("fonts/delius/Delius-Regular.ttf");
+    commands
+        .spawn((
+            EditorPanel,
+            Node {
+                display: Display::None,
+                position_type: PositionType::Absolute,
+                top: px(60),
+                left: px(20),
+                padding: UiRect::axes(px(20), px(10)),
+                ..default()
+            },
+            BackgroundColor(Color::linear_rgba(0.05, 0.05, 0.15, 0.8)),
+        ))
+        .with_children(|builder| {
+            builder.spawn((
+                EditorText,
+                Text::default(),
+                TextColor(Color::linear_rgb(1., 1., 1.)),
+                TextFont {
+                    font,
+                    font_size: 20.0,
+                    ..default()
+                },
+            ));
+        });
+}
+
+pub fn release_gameplay_actions_system(mut action_state: ResMut<ActionState>) {
+    for action in GAMEPLAY_ACTIONS {
+        action_state.release(action);
+    }
+}
+
+/// Starts editing, or leaves the editor and test-plays the edited level from the start.
+pub fn toggle_editor_system(
+    mut commands: Commands,
+    keys: Res<ButtonInput<KeyCode>>,
+    mut editor: ResMut<Editor>,
+    level: Res<Level>,
+    level_assets: Res<LevelAssets>,
+    mut jobs: ResMut<Jobs>,
+    mut pickup_history: ResMut<PickupHistory>,
+    spawned: Query<Entity, Or<(With<LevelEntity>, With<Client>, With<Commission>)>>,
+) {
+    if !keys.just_pressed(KeyCode::F2) {
+        return;
+    }
+    editor.editing = !editor.editing;
+    editor.glue_group = None;
+    if editor.editing {
+        editor.status = String::new();
+        return;
+    }
+
+    for entity in spawned.iter() {
+        commands.entity(entity).despawn();
+    }
+    *pickup_history = PickupHistory::default();
+    jobs.current = 0;
+    jobs.started = false;
+    spawn_level(&mut commands, &level, &level_assets);
+}
+
+pub fn hover_cell_system(
+    mut editor: ResMut<Editor>,
+    level: Res<Level>,
+    window: Query<&Window, With<PrimaryWindow>>,
+    camera: Query<(&Camera, &GlobalTransform), With<BillboardCamera>>,
+) {
+    editor.hovered = None;
+    let (Ok(window), Ok((camera, camera_transform))) = (window.single(), camera.single()) else {
+        return;
+    };
+    let Some(ray) = window
+        .cursor_position()
+        .and_then(|cursor| camera.viewport_to_world(camera_transform, cursor).ok())
+    else {
+        return;
+    };
+    let Some(distance) = ray.intersect_plane(Vec3::ZERO, InfinitePlane3d::new(Vec3::Y)) else {
+        return;
+    };
+    let cell = ray.get_point(distance).xz().round().as_ivec2();
+    editor.hovered = level.get(cell).map(|_| cell);
+}
+
+/// Takes `cells` out of their glue groups, dropping groups with nothing left to glue together.
+/// Returns the cells whose glue changed.
+fn unglue(level: &mut Level, cells: &[IVec2]) -> Vec<IVec2> {
+    let mut changed = Vec::new();
+    for group in &mut level.glue {
+        if group.iter().any(|cell| cells.contains(cell)) {
+            changed.extend(group.iter().copied());
+            group.retain(|cell| !cells.contains(cell));
+        }
+    }
+    level.glue.retain(|group| group.len() > 1);
+    changed
+}
+
+/// Changes the tile at `cell`. Returns the cells which need to be spawned again.
+fn paint(level: &mut Level, cell: IVec2, tile: LevelTile) -> Vec<IVec2> {
+    let Some(old) = level.get(cell) else {
+        return Vec::new();
+    };
+    if old == tile {
+        return Vec::new();
+    }
+    let mut changed = vec![cell];
+    // There is only ever one player.
+    if tile == LevelTile::Player {
+        let players: Vec<IVec2> = level
+            .cells()
+            .filter(|&(_, tile)| tile == LevelTile::Player)
+            .map(|(player, _)| player)
+            .collect();
+        for player in players {
+            level.set(player, LevelTile::Floor);
+            changed.push(player);
+        }
+    }
+    level.set(cell, tile);
+    changed.extend(unglue(level, &[cell]));
+    if old == LevelTile::Blueprint {
+        level.blueprints.retain(|(location, _)| *location != cell);
+    }
+    changed
+}
+
+/// Despawns and spawns again what the level has at each of `cells`, apart from the player, who
+/// stays where they are until the level is test-played.
+fn respawn_cells(
+    commands: &mut Commands,
+    level: &Level,
+    level_assets: &LevelAssets,
+    ground: &mut GroundGrid,
+    spawned: &Query<(Entity, &LevelEntity), Without<Player>>,
+    cells: &[IVec2],
+) {
+    for (entity, level_entity) in spawned.iter() {
+        if cells.contains(&level_entity.cell) {
+            commands.entity(entity).despawn();
+        }
+    }
+    for &cell in cells {
+        let Some(tile) = level.get(cell) else {
+            continue;
+        };
+        if tile == LevelTile::Water {
+            ground.set(cell, GroundTile::Water);
+        } else {
+            ground.set(cell, GroundTile::Floor);
+        }
+        if tile != LevelTile::Player {
+            spawn_level_cell(commands, level, level_assets, cell);
+        }
+    }
+}
+
+pub fn edit_level_system(
+    mut commands: Commands,
+    keys: Res<ButtonInput<KeyCode>>,
+    mouse_buttons: Res<ButtonInput<MouseButton>>,
+    mut editor: ResMut<Editor>,
+    mut level: ResMut<Level>,
+    level_assets: Res<LevelAssets>,
+    mut ground: ResMut<GroundGrid>,
+    spawned: Query<(Entity, &LevelEntity), Without<Player>>,
+) {
+    for (index, key) in BRUSH_KEYS.iter().enumerate() {
+        if keys.just_pressed(*key) {
+            editor.brush = index;
+        }
+    }
+
+    let control = keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+    if control && keys.just_pressed(KeyCode::KeyS) {
+        let errors: Vec<Diagnostic> = lint_level(&level)
+            .into_iter()
+            .filter(|diagnostic| diagnostic.severity == Severity::Error)
+            .collect();
+        // Level text keeps the glue groups and blueprint requirements a level image can't.
+        editor.status = if let Some(error) = errors.first() {
+            format!(
+                "Cannot save {}: {} ({} errors in all)",
+                LEVEL_TEXT_PATH,
+                error,
+                errors.len()
+            )
+        } else {
+            match level.save(LEVEL_TEXT_PATH) {
+                Ok(()) => format!("Saved {}", LEVEL_TEXT_PATH),
+                Err(err) => format!("Cannot save {}: {}", LEVEL_TEXT_PATH, err),
+            }
+        };
+    }
+
+    let mut changed = Vec::new();
+    if keys.just_pressed(KeyCode::KeyG) {
+        match editor.glue_group.take() {
+            None => editor.glue_group = Some(Vec::new()),
+            Some(group) => {
+                changed.extend(unglue(&mut level, &group));
+                if group.len() > 1 {
+                    changed.extend(group.iter().copied());
+                    level.glue.push(group);
+                }
+            }
+        }
+    }
+
+    if let Some(cell) = editor.hovered {
+        let brush = LevelTile::ALL[editor.brush];
+        let is_item = level.get(cell).is_some_and(|tile| tile.is_item());
+        if let Some(group) = &mut editor.glue_group {
+            if mouse_buttons.just_pressed(MouseButton::Left) && is_item {
+                if let Some(index) = group.iter().position(|&member| member == cell) {
+                    group.remove(index);
+                } else if group.len() < MAX_GLUE_GROUP {
+                    group.push(cell);
+                } else {
+                    editor.status =
+                        format!("A glue group can hold at most {} items", MAX_GLUE_GROUP);
+                }
+            }
+            if mouse_buttons.just_pressed(MouseButton::Right) {
+                changed.extend(unglue(&mut level, &[cell]));
+            }
+        } else if mouse_buttons.pressed(MouseButton::Left) {
+            changed.extend(paint(&mut level, cell, brush));
+        } else if mouse_buttons.pressed(MouseButton::Right) {
+            changed.extend(paint(&mut level, cell, LevelTile::Floor));
+        }
+    }
+
+    if !changed.is_empty() {
+        changed.sort_by_key(|cell| (cell.x, cell.y));
+        changed.dedup();
+        respawn_cells(
+            &mut commands,
+            &level,
+            &level_assets,
+            &mut ground,
+            &spawned,
+            &changed,
+        );
+    }
+}
+
+fn tile_color(tile: LevelTile) -> Color {
+    let [r, g, b] = tile.color();
+    Color::srgb_u8(r, g, b)
+}
+
+/// Outlines the cell under the mouse, the player's starting cell, and the glue groups.
+pub fn draw_editor_system(editor: Res<Editor>, level: Res<Level>, mut gizmos: Gizmos) {
+    let flat = Quat::from_rotation_x(std::f32::consts::FRAC_PI_2);
+    let at = |cell: IVec2| Vec3::new(cell.x as f32, 0.05, cell.y as f32);
+
+    if let Some(cell) = editor.hovered {
+        gizmos.rect(
+            Isometry3d::new(at(cell), flat),
+            Vec2::splat(1.),
+            tile_color(LevelTile::ALL[editor.brush]),
+        );
+    }
+    for (cell, tile) in level.cells() {
+        if tile == LevelTile::Player {
+            gizmos.circle(
+                Isometry3d::new(at(cell), flat),
+                0.4,
+                tile_color(LevelTile::Player),
+            );
+        }
+    }
+
+    let glue_color = Color::linear_rgb(1., 0.9, 0.2);
+    for group in &level.glue {
+        for pair in group.windows(2) {
+            gizmos.line(at(pair[0]), at(pair[1]), glue_color);
+        }
+    }
+    if let Some(group) = &editor.glue_group {
+        for &cell in group {
+            gizmos.rect(
+                Isometry3d::new(at(cell), flat),
+                Vec2::splat(0.8),
+                Color::WHITE,
+            );
+        }
+        for pair in group.windows(2) {
+            gizmos.line(at(pair[0]), at(pair[1]), Color::WHITE);
+        }
+    }
+}
+
+pub fn show_editor_ui_system(
+    editor: Res<Editor>,
+    mut panel: Query<&mut Node, With<EditorPanel>>,
+    mut text: Query<&mut Text, With<EditorText>>,
+) {
+    let (Ok(mut panel), Ok(mut text)) = (panel.single_mut(), text.single_mut()) else {
+        return;
+    };
+    if !editor.editing {
+        panel.display = Display::None;
+        return;
+    }
+    panel.display = Display::Flex;
+
+    let mut lines = vec!["Editing (F2 to test-play)".to_string(), String::new()];
+    for (index, tile) in LevelTile::ALL.iter().enumerate() {
+        let marker = if index == editor.brush { ">" } else { " " };
+        lines.push(format!("{} {} {:?}", marker, index + 1, tile));
+    }
+    lines.push(String::new());
+    lines.push("Left click to paint, right click to erase".to_string());
+    match &editor.glue_group {
+        None => lines.push("G to glue items together".to_string()),
+        Some(group) => {
+            lines.push(format!(
+                "Gluing {} items: click items to add them",
+                group.len()
+            ));
+            lines.push("G to finish, right click to unglue an item".to_string());
+        }
+    }
+    lines.push(format!("Ctrl+S to save {}", LEVEL_TEXT_PATH));
+    if let Some(cell) = editor.hovered {
+        lines.push(format!("({}, {})", cell.x, cell.y));
+    }
+    if !editor.status.is_empty() {
+        lines.push(String::new());
+        lines.push(editor.status.clone());
+    }
+    let joined = lines.join("\n");
+    if text.0 != joined {
+        text.0 = joined;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{blueprint::BlueprintRequirements, level_text::parse_level_text};
+
+    fn test_level() -> Level {
+        parse_level_text(
+            "[tiles]
+#####
+#B@f#
+#ff.#
+##D##
+[glue]
+(1, 2), (2, 2), (3, 1)
+[blueprints]
+(1, 1), (min_size: 2)
+",
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn painting_a_player_moves_the_player() {
+        let mut level = test_level();
+        let changed = paint(&mut level, IVec2::new(3, 2), LevelTile::Player);
+        assert_eq!(changed, vec![IVec2::new(3, 2), IVec2::new(2, 1)]);
+        let players: Vec<IVec2> = level
+            .cells()
+            .filter(|&(_, tile)| tile == LevelTile::Player)
+            .map(|(cell, _)| cell)
+            .collect();
+        assert_eq!(players, vec![IVec2::new(3, 2)]);
+    }
+
+    #[test]
+    fn ungluing_drops_groups_too_small_to_glue() {
+        let mut level = test_level();
+        unglue(&mut level, &[IVec2::new(3, 1)]);
+        assert_eq!(level.glue, vec![vec![IVec2::new(1, 2), IVec2::new(2, 2)]]);
+
+        // Painting over an item takes it out of its group, leaving a single fence.
+        paint(&mut level, IVec2::new(1, 2), LevelTile::Floor);
+        assert!(level.glue.is_empty());
+    }
+
+    #[test]
+    fn painting_over_a_blueprint_drops_its_requirements() {
+        let mut level = test_level();
+        assert_eq!(level.requirements(IVec2::new(1, 1)).min_size, 2);
+        paint(&mut level, IVec2::new(1, 1), LevelTile::Floor);
+        assert!(level.blueprints.is_empty());
+        assert_eq!(
+            level.requirements(IVec2::new(1, 1)),
+            BlueprintRequirements::default()
+        );
+    }
+}
@@ -0,0 +1,196 @@
+use std::fmt::Write;
+
+use bevy::{mesh::VertexAttributeValues, prelude::*};
+
+use crate::{
+    ground::{GroundGrid, GroundTile, build_ground_chunk_mesh},
+    level::{Level, LevelTile},
+    rooms::RoomGeometry,
+};
+
+/// Accumulates named objects into a Wavefront OBJ file.
+#[derive(Default)]
+pub struct ObjExport {
+    out: String,
+    position_count: usize,
+    uv_count: usize,
+    normal_count: usize,
+}
+
+impl ObjExport {
+    pub fn new() -> Self {
+        let mut export = Self::default();
+        export.out.push_str("# exported by export_mesh\n");
+        export
+    }
+
+    /// Writes one copy of `mesh` per entry of `offsets`, all into a single object called `name`.
+    pub fn add_mesh(&mut self, name: &str, mesh: &Mesh, offsets: &[Vec3]) {
+        let positions = mesh
+            .attribute(Mesh::ATTRIBUTE_POSITION)
+            .and_then(|attr| attr.as_float3())
+            .expect("mesh has positions");
+        let uvs = match mesh.attribute(Mesh::ATTRIBUTE_UV_0) {
+            Some(VertexAttributeValues::Float32x2(uvs)) => Some(uvs),
+            _ => None,
+        };
+        let normals = mesh
+            .attribute(Mesh::ATTRIBUTE_NORMAL)
+            .and_then(|attr| attr.as_float3());
+        let indices: Vec<usize> = match mesh.indices() {
+            Some(indices) => indices.iter().collect(),
+            None => (0..positions.len()).collect(),
+        };
+
+        writeln!(self.out, "o {}", name).unwrap();
+        for &offset in offsets {
+            for p in positions {
+                let p = Vec3::from_array(*p) + offset;
+                writeln!(self.out, "v {} {} {}", p.x, p.y, p.z).unwrap();
+            }
+            if let Some(uvs) = uvs {
+                for uv in uvs {
+                    // OBJ texture coordinates start at the bottom of the image.
+                    writeln!(self.out, "vt {} {}", uv[0], 1. - uv[1]).unwrap();
+                }
+            }
+            if let Some(normals) = normals {
+                for n in normals {
+                    writeln!(self.out, "vn {} {} {}", n[0], n[1], n[2]).unwrap();
+                }
+            }
+
+            for triangle in indices.chunks_exact(3) {
+                self.out.push('f');
+                for &i in triangle {
+                    let v = self.position_count + i + 1;
+                    let vt = self.uv_count + i + 1;
+                    let vn = self.normal_count + i + 1;
+                    match (uvs.is_some(), normals.is_some()) {
+                        (true, true) => write!(self.out, " {}/{}/{}", v, vt, vn),
+                        (true, false) => write!(self.out, " {}/{}", v, vt),
+                        (false, true) => write!(self.out, " {}//{}", v, vn),
+                        (false, false) => write!(self.out, " {}", v),
+                    }
+                    .unwrap();
+                }
+                self.out.push('\n');
+            }
+
+            self.position_count += positions.len();
+            if let Some(uvs) = uvs {
+                self.uv_count += uvs.len();
+            }
+            if let Some(normals) = normals {
+                self.normal_count += normals.len();
+            }
+        }
+    }
+
+    /// Writes an axis-aligned box as its own object, for collider shapes.
+    pub fn add_box(&mut self, name: &str, center: Vec3, size: Vec3) {
+        writeln!(self.out, "o {}", name).unwrap();
+        let half = size / 2.;
+        for i in 0..8 {
+            let corner = Vec3::new(
+                if i & 1 == 0 { -half.x } else { half.x },
+                if i & 2 == 0 { -half.y } else { half.y },
+                if i & 4 == 0 { -half.z } else { half.z },
+            ) + center;
+            writeln!(self.out, "v {} {} {}", corner.x, corner.y, corner.z).unwrap();
+        }
+        for face in [
+            [0, 2, 3, 1],
+            [4, 5, 7, 6],
+            [0, 1, 5, 4],
+            [2, 6, 7, 3],
+            [0, 4, 6, 2],
+            [1, 3, 7, 5],
+        ] {
+            self.out.push('f');
+            for corner in face {
+                write!(self.out, " {}", self.position_count + corner + 1).unwrap();
+            }
+            self.out.push('\n');
+        }
+        self.position_count += 8;
+    }
+
+    pub fn finish(self) -> String {
+        self.out
+    }
+}
+
+pub fn export_room(room: &RoomGeometry) -> ObjExport {
+    let mut export = ObjExport::new();
+    export.add_mesh("room", &room.mesh, &[Vec3::ZERO]);
+    for (i, collider_box) in room.colliders.iter().enumerate() {
+        export.add_box(
+            &format!("collider_{}", i),
+            collider_box.center,
+            collider_box.size,
+        );
+    }
+    export
+}
+
+/// Exports the same geometry that `setup` spawns for a level: the batched floor and water chunks,
+/// the wall cubes, and the ground and wall colliders.
+pub fn export_level(level: &Level) -> ObjExport {
+    let ground = GroundGrid::from_level(level);
+    let walls: Vec<Vec3> = level
+        .cells()
+        .filter(|&(_, tile)| tile == LevelTile::Wall)
+        .map(|(cell, _)| Vec3::new(cell.x as f32, 0.5, cell.y as f32))
+        .collect();
+
+    let mut export = ObjExport::new();
+
+    let mut chunks: Vec<IVec2> = ground.chunks().into_iter().collect();
+    chunks.sort_by_key(|chunk| (chunk.x, chunk.y));
+    for chunk in chunks {
+        for (tile, name) in [(GroundTile::Floor, "floor"), (GroundTile::Water, "water")] {
+            if let Some(mesh) = build_ground_chunk_mesh(&ground, chunk, tile) {
+                export.add_mesh(
+                    &format!("{}_chunk_{}_{}", name, chunk.x, chunk.y),
+                    &mesh,
+                    &[Vec3::ZERO],
+                );
+            }
+        }
+    }
+
+    export.add_mesh("walls", &Mesh::from(Cuboid::default()), &walls);
+
+    export.add_box(
+        "collider_ground",
+        Vec3::new(0., -0.5, 0.),
+        Vec3::new(1000., 1., 1000.),
+    );
+    for (i, &wall) in walls.iter().enumerate() {
+        export.add_box(&format!("collider_wall_{}", i), wall, Vec3::ONE);
+    }
+    export
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::level_text::parse_level_text;
+
+    #[test]
+    fn exports_a_tiny_level() {
+        // A wall, a floor tile and a water tile in a row.
+        let level = parse_level_text("[tiles]\n#.~\n").unwrap();
+        let obj = export_level(&level).finish();
+        let count = |prefix: &str| obj.lines().filter(|line| line.starts_with(prefix)).count();
+
+        // The floor chunk, the water chunk, the walls, and a collider box each for the ground and
+        // the wall.
+        assert_eq!(count("o "), 5);
+        // 8 floor quads, 4 water quads, a 24 vertex cube, and two boxes of 8 corners.
+        assert_eq!(count("v "), 8 * 4 + 4 * 4 + 24 + 2 * 8);
+        assert_eq!(count("vt "), 8 * 4 + 4 * 4 + 24);
+        assert_eq!(count("vn "), 8 * 4 + 4 * 4 + 24);
+    }
+}
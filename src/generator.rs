@@ -0,0 +1,614 @@
+//! Generates levels from a seed, for the daily challenge and endless modes.
+//!
+//! Each blueprint gets a walled room with a door and a gap in its walls, and somewhere else in the
+//! level there is a fence piece which fits the gap. Ponds and spare pieces are scattered around the
+//! rooms. Only layouts which the solver can finish are kept.
+
+use bevy::{
+    platform::collections::HashSet,
+    prelude::*,
+    tasks::{AsyncComputeTaskPool, Task, futures::check_ready},
+};
+use rand::{Rng, SeedableRng, rngs::StdRng};
+
+use crate::{
+    blueprint::{Blueprint, BlueprintRequirements, BlueprintStatus},
+    ground::GroundGrid,
+    item::PickupHistory,
+    jobs::LevelComplete,
+    level::{Level, LevelTile},
+    level_spawn::{LevelAssets, LevelEntity, spawn_level},
+    lint::{Severity, lint_level},
+    solver::{Move, Puzzle, SolveResult},
+};
+
+/// The most states the solver visits for a generated level. Each generated room is closed with one
+/// piece, so a layout which needs more than this is thrown away.
+const GENERATOR_MAX_STATES: usize = 20_000;
+
+/// How long the next endless level waits after the last one is finished.
+const NEXT_LEVEL_SECONDS: f32 = 2.;
+
+/// How many seeds in a row a challenge tries before giving up on generating a level.
+pub const MAX_CHALLENGE_SEEDS: u64 = 10;
+
+#[derive(Clone, Debug)]
+pub struct GeneratorOptions {
+    pub width: i32,
+    pub height: i32,
+    pub blueprints: usize,
+    pub ponds: usize,
+    /// Pieces which aren't needed to close any room.
+    pub spare_pieces: usize,
+    /// How many layouts to try before giving up on the seed.
+    pub max_attempts: usize,
+}
+
+impl Default for GeneratorOptions {
+    fn default() -> Self {
+        GeneratorOptions {
+            width: 24,
+            height: 20,
+            blueprints: 2,
+            ponds: 2,
+            spare_pieces: 3,
+            max_attempts: 100,
+        }
+    }
+}
+
+pub struct GeneratedLevel {
+    pub level: Level,
+    /// The moves the solver found to finish the level.
+    pub solution: Vec<Move>,
+}
+
+/// A rectangle of cells, from `min` to `max` inclusive.
+#[derive(Copy, Clone)]
+struct CellRect {
+    min: IVec2,
+    max: IVec2,
+}
+
+impl CellRect {
+    fn grow(self, by: i32) -> CellRect {
+        CellRect {
+            min: self.min - by,
+            max: self.max + by,
+        }
+    }
+
+    fn contains(self, cell: IVec2) -> bool {
+        cell.cmpge(self.min).all() && cell.cmple(self.max).all()
+    }
+
+    fn overlaps(self, other: CellRect) -> bool {
+        self.min.cmple(other.max).all() && other.min.cmple(self.max).all()
+    }
+
+    fn cells(self) -> impl Iterator<Item = IVec2> {
+        (self.min.x..=self.max.x)
+            .flat_map(move |x| (self.min.y..=self.max.y).map(move |y| IVec2::new(x, y)))
+    }
+}
+
+/// A level being laid out.
+struct Layout {
+    width: i32,
+    height: i32,
+    tiles: Vec<LevelTile>,
+    glue: Vec<Vec<IVec2>>,
+    blueprints: Vec<(IVec2, BlueprintRequirements)>,
+    /// Room walls, and the cells around them, which nothing else may be placed on.
+    rooms: Vec<CellRect>,
+    /// Pieces, and the cells around them, which nothing else may be placed on.
+    taken: HashSet<IVec2>,
+}
+
+impl Layout {
+    fn new(width: i32, height: i32) -> Layout {
+        Layout {
+            width,
+            height,
+            tiles: vec![LevelTile::Floor; (width * height) as usize],
+            glue: Vec::new(),
+            blueprints: Vec::new(),
+            rooms: Vec::new(),
+            taken: HashSet::new(),
+        }
+    }
+
+    fn get(&self, cell: IVec2) -> Option<LevelTile> {
+        if cell.x < 0 || cell.y < 0 || cell.x >= self.width || cell.y >= self.height {
+            return None;
+        }
+        Some(self.tiles[(cell.y * self.width + cell.x) as usize])
+    }
+
+    fn set(&mut self, cell: IVec2, tile: LevelTile) {
+        if self.get(cell).is_some() {
+            self.tiles[(cell.y * self.width + cell.x) as usize] = tile;
+        }
+    }
+
+    /// Whether `cell`, and every cell around it, is open floor that nothing has been placed on.
+    fn is_clear(&self, cell: IVec2) -> bool {
+        (-1..=1).all(|dx| {
+            (-1..=1).all(|dy| {
+                let around = cell + IVec2::new(dx, dy);
+                self.get(around) == Some(LevelTile::Floor)
+                    && !self.taken.contains(&around)
+                    && !self.rooms.iter().any(|room| room.contains(around))
+            })
+        })
+    }
+
+    fn random_cell(&self, rng: &mut StdRng) -> IVec2 {
+        IVec2::new(
+            rng.random_range(0..self.width),
+            rng.random_range(0..self.height),
+        )
+    }
+
+    /// A ring of water around the level, so the player can't walk off it.
+    fn add_shore(&mut self) {
+        let level = CellRect {
+            min: IVec2::ZERO,
+            max: IVec2::new(self.width - 1, self.height - 1),
+        };
+        for cell in level.cells() {
+            if !level.grow(-1).contains(cell) {
+                self.set(cell, LevelTile::Water);
+            }
+        }
+    }
+
+    fn add_pond(&mut self, rng: &mut StdRng) {
+        let center = self.random_cell(rng);
+        let radius: i32 = rng.random_range(1..=2);
+        for dx in -radius..=radius {
+            for dy in -radius..=radius {
+                if dx * dx + dy * dy <= radius * radius {
+                    self.set(center + IVec2::new(dx, dy), LevelTile::Water);
+                }
+            }
+        }
+    }
+
+    /// Adds a walled room around a blueprint, with a door and a gap in its walls. Returns the
+    /// length of the gap, or `None` if there wasn't space for the room.
+    fn add_room(&mut self, rng: &mut StdRng) -> Option<i32> {
+        let size = IVec2::new(rng.random_range(2..=4), rng.random_range(2..=4));
+        if self.width - 3 - size.x <= 3 || self.height - 3 - size.y <= 3 {
+            return None;
+        }
+        // Leave space for the shore and a path around the room.
+        let min = IVec2::new(
+            rng.random_range(3..self.width - 3 - size.x),
+            rng.random_range(3..self.height - 3 - size.y),
+        );
+        let interior = CellRect {
+            min,
+            max: min + size - 1,
+        };
+        let walls = interior.grow(1);
+        if self.rooms.iter().any(|room| room.overlaps(walls.grow(1))) {
+            return None;
+        }
+
+        for cell in walls.grow(1).cells() {
+            self.set(cell, LevelTile::Floor);
+        }
+        for cell in walls.cells() {
+            if !interior.contains(cell) {
+                self.set(cell, LevelTile::Wall);
+            }
+        }
+
+        // The walls along each side, without the corners.
+        let sides: [Vec<IVec2>; 4] = [
+            (min.x..=interior.max.x)
+                .map(|x| IVec2::new(x, walls.min.y))
+                .collect(),
+            (min.x..=interior.max.x)
+                .map(|x| IVec2::new(x, walls.max.y))
+                .collect(),
+            (min.y..=interior.max.y)
+                .map(|y| IVec2::new(walls.min.x, y))
+                .collect(),
+            (min.y..=interior.max.y)
+                .map(|y| IVec2::new(walls.max.x, y))
+                .collect(),
+        ];
+        let gap_side = rng.random_range(0..4);
+        let door_side = (gap_side + rng.random_range(1..4)) % 4;
+
+        let side = &sides[gap_side];
+        let gap = rng.random_range(1..=side.len().min(3));
+        let start = rng.random_range(0..=side.len() - gap);
+        for &cell in &side[start..start + gap] {
+            self.set(cell, LevelTile::Floor);
+        }
+        let door = sides[door_side][rng.random_range(0..sides[door_side].len())];
+        self.set(door, LevelTile::Door);
+
+        let blueprint = IVec2::new(
+            rng.random_range(interior.min.x..=interior.max.x),
+            rng.random_range(interior.min.y..=interior.max.y),
+        );
+        self.set(blueprint, LevelTile::Blueprint);
+        // Sometimes the room has to be closed right at its walls, rather than further out.
+        if rng.random_bool(0.5) {
+            self.blueprints.push((
+                blueprint,
+                BlueprintRequirements {
+                    max_size: Some(interior.cells().count()),
+                    ..default()
+                },
+            ));
+        }
+
+        self.rooms.push(walls.grow(1));
+        Some(gap as i32)
+    }
+
+    /// Adds a straight piece of `length` glued items somewhere clear. Returns whether there was
+    /// space for it.
+    fn add_piece(&mut self, rng: &mut StdRng, tile: LevelTile, length: i32) -> bool {
+        for _ in 0..50 {
+            let anchor = self.random_cell(rng);
+            let dir = if rng.random_bool(0.5) {
+                IVec2::X
+            } else {
+                IVec2::Y
+            };
+            let cells: Vec<IVec2> = (0..length).map(|index| anchor + dir * index).collect();
+            if !cells.iter().all(|&cell| self.is_clear(cell)) {
+                continue;
+            }
+            for &cell in &cells {
+                self.set(cell, tile);
+                for dx in -1..=1 {
+                    for dy in -1..=1 {
+                        self.taken.insert(cell + IVec2::new(dx, dy));
+                    }
+                }
+            }
+            if cells.len() > 1 {
+                self.glue.push(cells);
+            }
+            return true;
+        }
+        false
+    }
+
+    fn add_player(&mut self, rng: &mut StdRng) -> bool {
+        for _ in 0..50 {
+            let cell = self.random_cell(rng);
+            if self.is_clear(cell) {
+                self.set(cell, LevelTile::Player);
+                return true;
+            }
+        }
+        false
+    }
+
+    fn finish(self) -> Level {
+        Level {
+            width: self.width as u32,
+            height: self.height as u32,
+            tiles: self.tiles,
+            glue: self.glue,
+            blueprints: self.blueprints,
+        }
+    }
+}
+
+/// Lays out one level, which may or may not be solvable.
+fn lay_out(rng: &mut StdRng, options: &GeneratorOptions) -> Option<Level> {
+    let mut layout = Layout::new(options.width, options.height);
+    layout.add_shore();
+    for _ in 0..options.ponds {
+        layout.add_pond(rng);
+    }
+    let mut gaps = Vec::new();
+    for _ in 0..options.blueprints {
+        gaps.push(layout.add_room(rng)?);
+    }
+    for gap in gaps {
+        if !layout.add_piece(rng, LevelTile::Fence, gap) {
+            return None;
+        }
+    }
+    for _ in 0..options.spare_pieces {
+        let (tile, length) = if rng.random_bool(0.5) {
+            (LevelTile::BrickWall, 1)
+        } else {
+            (LevelTile::Fence, rng.random_range(1..=3))
+        };
+        layout.add_piece(rng, tile, length);
+    }
+    if !layout.add_player(rng) {
+        return None;
+    }
+    Some(layout.finish())
+}
+
+/// Generates a level which the solver can finish. The same seed always gives the same level.
+pub fn generate_level(seed: u64, options: &GeneratorOptions) -> Option<GeneratedLevel> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    for _ in 0..options.max_attempts {
+        let Some(level) = lay_out(&mut rng, options) else {
+            continue;
+        };
+        if lint_level(&level)
+            .iter()
+            .any(|diagnostic| diagnostic.severity == Severity::Error)
+        {
+            continue;
+        }
+        let Ok(puzzle) = Puzzle::new(&level, &[]) else {
+            continue;
+        };
+        // A level that is already finished isn't much of a puzzle.
+        if let SolveResult::Solved(solution) = puzzle.solve_within(GENERATOR_MAX_STATES)
+            && !solution.is_empty()
+        {
+            return Some(GeneratedLevel { level, solution });
+        }
+    }
+    None
+}
+
+/// Generates a level from the first seed, starting at `seed`, which has one, trying at most
+/// [`MAX_CHALLENGE_SEEDS`] seeds. Returns the seed used, along with its level.
+pub fn generate_level_from(seed: u64, options: &GeneratorOptions) -> Option<(u64, GeneratedLevel)> {
+    (0..MAX_CHALLENGE_SEEDS)
+        .map(|offset| seed.wrapping_add(offset))
+        .find_map(|seed| Some((seed, generate_level(seed, options)?)))
+}
+
+/// The seed for today's daily challenge, which is the same for everyone playing on the same day.
+pub fn daily_seed() -> u64 {
+    let since_epoch = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default();
+    since_epoch.as_secs() / (24 * 60 * 60)
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum ChallengeMode {
+    /// One level, the same for everyone on the same day.
+    Daily,
+    /// A new level each time one is finished.
+    Endless,
+}
+
+/// Plays generated levels instead of the level on disk, which have no clients or jobs.
+#[derive(Resource, Clone, Debug)]
+pub struct Challenge {
+    pub mode: ChallengeMode,
+    /// The seed of the level being played.
+    pub seed: u64,
+    /// How many levels have been finished.
+    pub cleared: u32,
+    /// Counts down to the next level, once the current one is finished.
+    pub next_level_in: Option<f32>,
+    /// Set once there are no more levels to play: the daily level was finished, or the next endless
+    /// level couldn't be generated.
+    pub over: bool,
+}
+
+impl Challenge {
+    /// Reads `--daily`, or `--endless` with an optional `--seed <seed>`, from the command line.
+    pub fn from_args(args: &[String]) -> Option<Challenge> {
+        let seed = args
+            .iter()
+            .position(|arg| arg == "--seed")
+            .and_then(|index| args.get(index + 1))
+            .and_then(|seed| seed.parse().ok());
+        let (mode, seed) = if args.iter().any(|arg| arg == "--daily") {
+            (ChallengeMode::Daily, daily_seed())
+        } else if args.iter().any(|arg| arg == "--endless") {
+            (ChallengeMode::Endless, seed.unwrap_or_else(rand::random))
+        } else {
+            return None;
+        };
+        Some(Challenge {
+            mode,
+            seed,
+            cleared: 0,
+            next_level_in: None,
+            over: false,
+        })
+    }
+
+    /// Generates the level for the seed, moving on to the next seeds if there isn't one, up to
+    /// [`MAX_CHALLENGE_SEEDS`] of them.
+    pub fn generate(&mut self) -> Option<Level> {
+        let (seed, generated) = generate_level_from(self.seed, &GeneratorOptions::default())?;
+        self.seed = seed;
+        Some(generated.level)
+    }
+}
+
+/// The next endless level, generated in the background while the last one is celebrated.
+#[derive(Resource)]
+pub struct NextChallengeLevel(Task<Option<(u64, GeneratedLevel)>>);
+
+pub struct ChallengePlugin;
+
+impl Plugin for ChallengePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, setup_challenge_system)
+            .add_systems(
+                Update,
+                (challenge_progress_system, show_challenge_system)
+                    .chain()
+                    .run_if(resource_exists::<Challenge>),
+            );
+    }
+}
+
+#[derive(Component)]
+pub struct ChallengeText;
+
+pub fn setup_challenge_system(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let font = asset_server.load("fonts/delius/Delius-Regular.ttf");
+    commands.spawn((
+        ChallengeText,
+        Text::default(),
+        TextColor(Color::linear_rgb(1., 0.9, 0.3)),
+        TextFont {
+            font,
+            font_size: 30.0,
+            ..default()
+        },
+        Node {
+            position_type: PositionType::Absolute,
+            top: px(10),
+            left: px(20),
+            ..default()
+        },
+    ));
+}
+
+/// Finishes the level once every blueprint is valid, and moves on to the next endless level.
+pub fn challenge_progress_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut challenge: ResMut<Challenge>,
+    mut level: ResMut<Level>,
+    level_assets: Res<LevelAssets>,
+    mut ground: ResMut<GroundGrid>,
+    mut pickup_history: ResMut<PickupHistory>,
+    blueprints: Query<Option<&BlueprintStatus>, With<Blueprint>>,
+    spawned: Query<Entity, With<LevelEntity>>,
+    mut level_complete: MessageWriter<LevelComplete>,
+    next_level: Option<ResMut<NextChallengeLevel>>,
+) {
+    if let Some(remaining) = challenge.next_level_in {
+        if remaining > 0. {
+            challenge.next_level_in = Some(remaining - time.delta_secs());
+            return;
+        }
+        // Wait for the generator to catch up, if it hasn't yet.
+        let Some(mut next_level) = next_level else {
+            return;
+        };
+        let Some(generated) = check_ready(&mut next_level.0) else {
+            return;
+        };
+        commands.remove_resource::<NextChallengeLevel>();
+        challenge.next_level_in = None;
+        let Some((seed, generated)) = generated else {
+            warn!(
+                "no level for the {} seeds after {}",
+                MAX_CHALLENGE_SEEDS, challenge.seed
+            );
+            challenge.over = true;
+            return;
+        };
+        challenge.seed = seed;
+        *level = generated.level;
+        *ground = GroundGrid::from_level(&level);
+        commands.insert_resource(level.bounds());
+        *pickup_history = PickupHistory::default();
+        for entity in spawned.iter() {
+            commands.entity(entity).despawn();
+        }
+        spawn_level(&mut commands, &level, &level_assets);
+        return;
+    }
+
+    let all_valid = !blueprints.is_empty()
+        && blueprints
+            .iter()
+            .all(|status| status.is_some_and(|status| status.is_valid()));
+    if challenge.over || !all_valid {
+        return;
+    }
+    challenge.cleared += 1;
+    level_complete.write(LevelComplete);
+    match challenge.mode {
+        ChallengeMode::Daily => challenge.over = true,
+        ChallengeMode::Endless => {
+            // Generating can take a while, so it happens off the main thread.
+            let seed = challenge.seed.wrapping_add(1);
+            let task = AsyncComputeTaskPool::get()
+                .spawn(async move { generate_level_from(seed, &GeneratorOptions::default()) });
+            commands.insert_resource(NextChallengeLevel(task));
+            challenge.next_level_in = Some(NEXT_LEVEL_SECONDS);
+        }
+    }
+}
+
+pub fn show_challenge_system(
+    challenge: Res<Challenge>,
+    mut text: Query<&mut Text, With<ChallengeText>>,
+) {
+    if !challenge.is_changed() {
+        return;
+    }
+    let Ok(mut text) = text.single_mut() else {
+        return;
+    };
+    text.0 = match challenge.mode {
+        ChallengeMode::Daily if challenge.cleared > 0 => "Daily challenge complete!".to_string(),
+        ChallengeMode::Daily => "Daily challenge".to_string(),
+        ChallengeMode::Endless if challenge.over => {
+            format!("Endless: {} cleared, and out of levels", challenge.cleared)
+        }
+        ChallengeMode::Endless => format!("Endless: {} cleared", challenge.cleared),
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SEEDS: [u64; 3] = [1, 2, 3];
+
+    fn generate(seed: u64) -> GeneratedLevel {
+        generate_level(seed, &GeneratorOptions::default())
+            .unwrap_or_else(|| panic!("no level for seed {}", seed))
+    }
+
+    #[test]
+    fn same_seed_same_level() {
+        for seed in SEEDS {
+            let (first, second) = (generate(seed), generate(seed));
+            assert_eq!(first.level.tiles, second.level.tiles);
+            assert_eq!(first.level.glue, second.level.glue);
+            assert_eq!(first.solution, second.solution);
+        }
+    }
+
+    #[test]
+    fn solution_finishes_the_level() {
+        for seed in SEEDS {
+            let generated = generate(seed);
+            let puzzle = Puzzle::new(&generated.level, &[]).unwrap();
+            assert!(!puzzle.is_finished(&puzzle.start));
+            let mut state = puzzle.start.clone();
+            for &solution_move in &generated.solution {
+                state = puzzle
+                    .apply(&state, solution_move)
+                    .unwrap_or_else(|| panic!("seed {}: can't play {:?}", seed, solution_move));
+            }
+            assert!(puzzle.is_finished(&state), "seed {}", seed);
+        }
+    }
+
+    #[test]
+    fn generated_levels_lint_clean() {
+        for seed in SEEDS {
+            let errors: Vec<String> = lint_level(&generate(seed).level)
+                .iter()
+                .filter(|diagnostic| diagnostic.severity == Severity::Error)
+                .map(|diagnostic| diagnostic.to_string())
+                .collect();
+            assert!(errors.is_empty(), "seed {}: {:?}", seed, errors);
+        }
+    }
+}
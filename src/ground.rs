@@ -0,0 +1,313 @@
+use bevy::{
+    asset::RenderAssetUsages,
+    platform::collections::{HashMap, HashSet},
+    prelude::*,
+};
+
+use crate::level::{Level, LevelTile};
+
+/// The width and depth, in tiles, of each batched ground mesh.
+pub const CHUNK_SIZE: i32 = 16;
+
+pub struct GroundPlugin;
+
+impl Plugin for GroundPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<GroundGrid>()
+            .add_systems(Startup, setup_ground_system)
+            .add_systems(Update, rebuild_ground_chunks_system);
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum GroundTile {
+    Floor,
+    Water,
+}
+
+impl GroundTile {
+    /// The height of the top face of this tile.
+    fn top(self) -> f32 {
+        match self {
+            GroundTile::Floor => 0.,
+            GroundTile::Water => -0.25,
+        }
+    }
+
+    fn bottom(self) -> f32 {
+        match self {
+            GroundTile::Floor => -1.,
+            GroundTile::Water => -1.25,
+        }
+    }
+}
+
+/// The floor and water tiles of the level. Rendering is batched into one mesh per chunk and tile
+/// type, which is rebuilt whenever a tile in (or bordering) that chunk changes.
+#[derive(Resource, Default)]
+pub struct GroundGrid {
+    tiles: HashMap<IVec2, GroundTile>,
+    dirty_chunks: HashSet<IVec2>,
+}
+
+pub fn chunk_of(cell: IVec2) -> IVec2 {
+    cell.div_euclid(IVec2::splat(CHUNK_SIZE))
+}
+
+impl GroundGrid {
+    pub fn from_level(level: &Level) -> GroundGrid {
+        let mut grid = GroundGrid::default();
+        for (cell, tile) in level.cells() {
+            if tile == LevelTile::Water {
+                grid.set(cell, GroundTile::Water);
+            } else {
+                grid.set(cell, GroundTile::Floor);
+            }
+        }
+        grid
+    }
+
+    pub fn get(&self, cell: IVec2) -> Option<GroundTile> {
+        self.tiles.get(&cell).copied()
+    }
+
+    pub fn is_water(&self, cell: IVec2) -> bool {
+        self.get(cell) == Some(GroundTile::Water)
+    }
+
+    pub fn water_cells(&self) -> impl Iterator<Item = IVec2> + '_ {
+        self.tiles
+            .iter()
+            .filter(|(_, tile)| **tile == GroundTile::Water)
+            .map(|(cell, _)| *cell)
+    }
+
+    pub fn set(&mut self, cell: IVec2, tile: GroundTile) {
+        if self.tiles.insert(cell, tile) == Some(tile) {
+            return;
+        }
+        // Side faces depend on the neighboring tiles, so neighboring chunks may need to change too.
+        for dir in [IVec2::ZERO, IVec2::X, IVec2::Y, IVec2::NEG_X, IVec2::NEG_Y] {
+            self.dirty_chunks.insert(chunk_of(cell + dir));
+        }
+    }
+
+    pub fn remove(&mut self, cell: IVec2) {
+        if self.tiles.remove(&cell).is_none() {
+            return;
+        }
+        for dir in [IVec2::ZERO, IVec2::X, IVec2::Y, IVec2::NEG_X, IVec2::NEG_Y] {
+            self.dirty_chunks.insert(chunk_of(cell + dir));
+        }
+    }
+
+    pub fn chunks(&self) -> HashSet<IVec2> {
+        self.tiles.keys().map(|&cell| chunk_of(cell)).collect()
+    }
+}
+
+/// Builds the mesh for all tiles of type `tile` in `chunk`, or `None` if there are no such tiles.
+///
+/// Only faces which can be seen are generated: the top of each tile, and the sides which are not
+/// covered by a neighbor at least as tall.
+pub fn build_ground_chunk_mesh(grid: &GroundGrid, chunk: IVec2, tile: GroundTile) -> Option<Mesh> {
+    let mut positions: Vec<Vec3> = Vec::new();
+    let mut normals: Vec<Vec3> = Vec::new();
+    let mut uvs: Vec<Vec2> = Vec::new();
+    let mut indices: Vec<u32> = Vec::new();
+
+    let mut add_quad = |corners: [Vec3; 4], normal: Vec3| {
+        let base = positions.len() as u32;
+        positions.extend(corners);
+        normals.extend([normal; 4]);
+        uvs.extend([
+            Vec2::new(0., 0.),
+            Vec2::new(1., 0.),
+            Vec2::new(1., 1.),
+            Vec2::new(0., 1.),
+        ]);
+        indices.extend([base, base + 1, base + 2, base, base + 2, base + 3]);
+    };
+
+    for x in 0..CHUNK_SIZE {
+        for y in 0..CHUNK_SIZE {
+            let cell = chunk * CHUNK_SIZE + IVec2::new(x, y);
+            if grid.get(cell) != Some(tile) {
+                continue;
+            }
+
+            let center = Vec3::new(cell.x as f32, 0., cell.y as f32);
+            let top = tile.top();
+
+            add_quad(
+                [
+                    center + Vec3::new(-0.5, top, -0.5),
+                    center + Vec3::new(-0.5, top, 0.5),
+                    center + Vec3::new(0.5, top, 0.5),
+                    center + Vec3::new(0.5, top, -0.5),
+                ],
+                Vec3::Y,
+            );
+
+            for dir in [IVec2::X, IVec2::Y, IVec2::NEG_X, IVec2::NEG_Y] {
+                let bottom = match grid.get(cell + dir) {
+                    Some(neighbor) => neighbor.top(),
+                    None => tile.bottom(),
+                };
+                if bottom >= top {
+                    continue;
+                }
+
+                let normal = Vec3::new(dir.x as f32, 0., dir.y as f32);
+                let side = Vec3::new(-normal.z, 0., normal.x);
+                let face = center + normal * 0.5;
+                add_quad(
+                    [
+                        face + side * 0.5 + Vec3::Y * top,
+                        face + side * 0.5 + Vec3::Y * bottom,
+                        face - side * 0.5 + Vec3::Y * bottom,
+                        face - side * 0.5 + Vec3::Y * top,
+                    ],
+                    normal,
+                );
+            }
+        }
+    }
+
+    if indices.is_empty() {
+        return None;
+    }
+
+    let mut mesh = Mesh::new(
+        bevy::mesh::PrimitiveTopology::TriangleList,
+        RenderAssetUsages::all(),
+    );
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+    mesh.insert_indices(bevy::mesh::Indices::U32(indices));
+    Some(mesh)
+}
+
+#[derive(Component)]
+pub struct GroundChunk {
+    pub chunk: IVec2,
+}
+
+#[derive(Resource)]
+pub struct GroundMaterials {
+    pub floor: Handle<StandardMaterial>,
+    pub water: Handle<StandardMaterial>,
+}
+
+pub fn setup_ground_system(
+    mut commands: Commands,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let floor = materials.add(StandardMaterial {
+        base_color: Color::linear_rgb(0.7, 0.9, 0.8),
+        perceptual_roughness: 1.,
+        ..default()
+    });
+
+    let water = materials.add(StandardMaterial {
+        base_color: Color::linear_rgb(0.2, 0.4, 0.6),
+        perceptual_roughness: 0.25,
+        ..default()
+    });
+
+    commands.insert_resource(GroundMaterials { floor, water });
+}
+
+pub fn rebuild_ground_chunks_system(
+    mut commands: Commands,
+    mut grid: ResMut<GroundGrid>,
+    ground_materials: Res<GroundMaterials>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    chunks: Query<(Entity, &GroundChunk, &Mesh3d)>,
+) {
+    if grid.dirty_chunks.is_empty() {
+        return;
+    }
+    let dirty_chunks = std::mem::take(&mut grid.dirty_chunks);
+
+    for (chunk_entity, chunk, mesh) in chunks.iter() {
+        if dirty_chunks.contains(&chunk.chunk) {
+            meshes.remove(&mesh.0);
+            commands.entity(chunk_entity).despawn();
+        }
+    }
+
+    for &chunk in &dirty_chunks {
+        for (tile, material) in [
+            (GroundTile::Floor, &ground_materials.floor),
+            (GroundTile::Water, &ground_materials.water),
+        ] {
+            let Some(mesh) = build_ground_chunk_mesh(&grid, chunk, tile) else {
+                continue;
+            };
+            commands.spawn((
+                GroundChunk { chunk },
+                Mesh3d(meshes.add(mesh)),
+                MeshMaterial3d(material.clone()),
+                Transform::default(),
+            ));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quad_count(mesh: &Mesh) -> usize {
+        mesh.indices().expect("ground meshes are indexed").len() / 6
+    }
+
+    #[test]
+    fn set_dirties_only_the_chunk_of_an_inner_tile() {
+        let mut grid = GroundGrid::default();
+        grid.set(IVec2::new(5, 5), GroundTile::Floor);
+        assert_eq!(grid.dirty_chunks, HashSet::from_iter([IVec2::ZERO]));
+
+        // Setting a tile to what it already is doesn't dirty anything.
+        grid.dirty_chunks.clear();
+        grid.set(IVec2::new(5, 5), GroundTile::Floor);
+        assert!(grid.dirty_chunks.is_empty());
+    }
+
+    #[test]
+    fn set_on_a_chunk_edge_dirties_the_neighboring_chunk() {
+        let mut grid = GroundGrid::default();
+        grid.set(IVec2::new(CHUNK_SIZE - 1, 5), GroundTile::Water);
+        assert_eq!(
+            grid.dirty_chunks,
+            HashSet::from_iter([IVec2::ZERO, IVec2::X])
+        );
+
+        grid.dirty_chunks.clear();
+        grid.remove(IVec2::new(CHUNK_SIZE - 1, 5));
+        assert_eq!(
+            grid.dirty_chunks,
+            HashSet::from_iter([IVec2::ZERO, IVec2::X])
+        );
+    }
+
+    #[test]
+    fn chunk_meshes_only_have_visible_faces() {
+        // A floor tile with water to its right.
+        let mut grid = GroundGrid::default();
+        grid.set(IVec2::new(0, 0), GroundTile::Floor);
+        grid.set(IVec2::new(1, 0), GroundTile::Water);
+
+        // The floor's top and all four sides, since the water is lower.
+        let floor = build_ground_chunk_mesh(&grid, IVec2::ZERO, GroundTile::Floor).unwrap();
+        assert_eq!(quad_count(&floor), 5);
+
+        // The water's top and three sides; the floor covers the fourth.
+        let water = build_ground_chunk_mesh(&grid, IVec2::ZERO, GroundTile::Water).unwrap();
+        assert_eq!(quad_count(&water), 4);
+
+        assert!(build_ground_chunk_mesh(&grid, IVec2::X, GroundTile::Floor).is_none());
+    }
+}
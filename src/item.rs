@@ -4,7 +4,10 @@ use bevy::{
 };
 
 use crate::{
-    billboard::Billboard,
+    audio::{PlaySound, SoundEffect},
+    billboard::{Billboard, Facing},
+    controls::{Action, ActionState, game_running},
+    level::MAX_GLUE_GROUP,
     player::{Player, Wall, WallGrid, gather_walls_system},
 };
 
@@ -13,7 +16,18 @@ pub struct ItemPlugin;
 impl Plugin for ItemPlugin {
     fn build(&self, app: &mut App) {
         app.add_systems(Startup, setup_grab_system)
-            .add_systems(Update, grab_item_system.after(gather_walls_system));
+            .init_resource::<PickupHistory>()
+            .add_systems(
+                Update,
+                (
+                    rotate_held_items_system,
+                    undo_pickup_system,
+                    grab_item_system,
+                )
+                    .chain()
+                    .after(gather_walls_system)
+                    .run_if(game_running),
+            );
     }
 }
 
@@ -23,6 +37,18 @@ pub struct Item {
     pub is_held: Option<IVec2>,
 }
 
+/// Where the currently held items were picked up from, so the pickup can be undone.
+#[derive(Resource, Default)]
+pub struct PickupHistory {
+    pub items: Vec<PickedUpItem>,
+}
+
+pub struct PickedUpItem {
+    pub entity: Entity,
+    pub position: IVec2,
+    pub glued: Vec<IVec2>,
+}
+
 #[derive(Component)]
 pub struct GrabIcon;
 
@@ -41,19 +67,21 @@ pub fn setup_grab_system(mut commands: Commands) {
             GrabIcon,
             Billboard {
                 image: "grab_icon.png".to_string(),
+                ..default()
             },
             Transform::from_scale(Vec3::splat(1.)),
         ))
         .id();
     commands.insert_resource(GrabIconEntity(grab_icon));
 
-    let point_icons = (0..10)
+    let point_icons = (0..MAX_GLUE_GROUP)
         .map(|_| {
             commands
                 .spawn((
                     PointIcon,
                     Billboard {
                         image: "point_icon.png".to_string(),
+                        ..default()
                     },
                     Transform::from_scale(Vec3::splat(0.)),
                 ))
@@ -63,20 +91,66 @@ pub fn setup_grab_system(mut commands: Commands) {
     commands.insert_resource(PointIconEntity(point_icons));
 }
 
+/// Turns the held items a quarter turn around the cursor, along with the glue between them.
+pub fn rotate_held_items_system(action_state: Res<ActionState>, mut items: Query<&mut Item>) {
+    if !action_state.just_pressed(Action::Rotate) {
+        return;
+    }
+    for mut item in items.iter_mut() {
+        let Some(hold_offset) = item.is_held else {
+            continue;
+        };
+        item.is_held = Some(hold_offset.perp());
+        for glue_offset in item.glued.iter_mut() {
+            *glue_offset = glue_offset.perp();
+        }
+    }
+}
+
+/// Puts the held items back where they were picked up.
+pub fn undo_pickup_system(
+    action_state: Res<ActionState>,
+    mut pickup_history: ResMut<PickupHistory>,
+    mut items: Query<(&mut Transform, &mut Item)>,
+    mut is_wall: Query<&mut Wall>,
+) {
+    if !action_state.just_pressed(Action::Undo) {
+        return;
+    }
+    for picked_up in pickup_history.items.drain(..) {
+        let Ok((mut item_transform, mut item)) = items.get_mut(picked_up.entity) else {
+            continue;
+        };
+        if item.is_held.is_none() {
+            continue;
+        }
+        item_transform.translation =
+            Vec3::new(picked_up.position.x as f32, 0., picked_up.position.y as f32);
+        item.is_held = None;
+        item.glued = picked_up.glued;
+        if let Ok(mut wall) = is_wall.get_mut(picked_up.entity) {
+            wall.enabled = true;
+        }
+    }
+}
+
 pub fn grab_item_system(
     time: Res<Time>,
     mut items: Query<(Entity, &mut Transform, &mut Item)>,
-    player: Query<(&Transform, &Player), Without<Item>>,
-    key: Res<ButtonInput<KeyCode>>,
+    player: Query<(&Transform, &Player, &Facing), Without<Item>>,
+    mut item_facings: Query<&mut Facing, With<Item>>,
+    action_state: Res<ActionState>,
+    mut pickup_history: ResMut<PickupHistory>,
     walls: Res<WallGrid>,
     mut is_wall: Query<&mut Wall>,
     grab_icon: Res<GrabIconEntity>,
     point_icon: Res<PointIconEntity>,
     mut arbitrary_transform: Query<&mut Transform, (Without<Player>, Without<Item>)>,
+    mut sounds: MessageWriter<PlaySound>,
 ) {
     let dt = time.delta_secs();
 
-    let Ok((player_transform, player)) = player.single() else {
+    let Ok((player_transform, player, player_facing)) = player.single() else {
         return;
     };
 
@@ -124,8 +198,8 @@ pub fn grab_item_system(
     let mut can_place_item = true;
     for (&item_offset, item_type) in cursor_place_offsets.iter() {
         let place_at = player_cursor + item_offset;
-        if walls.walls.contains(&place_at) {
-            // The held item is blocked by a wall.
+        if walls.is_blocked(place_at) {
+            // The held item is blocked by a wall or water.
             can_place_item = false;
             break;
         }
@@ -152,7 +226,9 @@ pub fn grab_item_system(
         let mut keys_sorted = cursor_place_offsets.keys().copied().collect::<Vec<IVec2>>();
         keys_sorted.sort_by_key(|v| (v.x, v.y));
         for hold_offset in keys_sorted {
-            let point_icon_entity = point_icon.0[set_point_icon_index];
+            let Some(&point_icon_entity) = point_icon.0.get(set_point_icon_index) else {
+                break;
+            };
             set_point_icon_index += 1;
 
             let icon_transform = &mut *arbitrary_transform.get_mut(point_icon_entity).unwrap();
@@ -165,7 +241,7 @@ pub fn grab_item_system(
                 player_cursor.x as f32 + hold_offset.x as f32,
                 0.,
                 player_cursor.y as f32 + hold_offset.y as f32,
-            ) + Vec3::Y * 0.5;
+            );
 
             if icon_transform.translation.distance(target_position) > 3.7 {
                 icon_transform.translation = target_position;
@@ -177,24 +253,30 @@ pub fn grab_item_system(
         }
     }
 
+    let mut placed = false;
     for (item_entity, mut item_transform, mut item) in items.iter_mut() {
         if let Some(hold_offset) = item.is_held {
             is_holding = true;
             item_transform.translation = item_transform.translation.lerp(
                 player_transform.translation
-                    + Vec3::Y * 0.75
+                    + Vec3::Y * 0.25
                     + Vec3::new(hold_offset.x as f32, 0., hold_offset.y as f32),
                 (dt * 15.).min(1.),
             );
+            if let Ok(mut item_facing) = item_facings.get_mut(item_entity) {
+                *item_facing = *player_facing;
+            }
 
             // Attempt to place at the cursor position, assuming there is room.
             if can_place_item {
                 let place_at = player_cursor + hold_offset;
 
-                if key.just_pressed(KeyCode::KeyE) {
+                if action_state.just_pressed(Action::Interact) {
+                    pickup_history.items.clear();
                     item_transform.translation =
-                        Vec3::Y * 0.5 + Vec3::new(place_at.x as f32, 0., place_at.y as f32);
+                        Vec3::new(place_at.x as f32, 0., place_at.y as f32);
                     item.is_held = None;
+                    placed = true;
 
                     if let Ok(mut wall) = is_wall.get_mut(item_entity) {
                         wall.enabled = true;
@@ -204,6 +286,12 @@ pub fn grab_item_system(
         }
     }
 
+    if placed {
+        sounds.write(PlaySound(SoundEffect::Place));
+    } else if is_holding && action_state.just_pressed(Action::Interact) {
+        sounds.write(PlaySound(SoundEffect::Blocked));
+    }
+
     let mut set_icon_grab = false;
 
     struct PickUp {
@@ -219,7 +307,7 @@ pub fn grab_item_system(
                 set_icon_grab = true;
             }
 
-            if !is_holding && key.just_pressed(KeyCode::KeyE) {
+            if !is_holding && action_state.just_pressed(Action::Interact) {
                 let mut cursor_offsets = item.glued.clone();
                 cursor_offsets.push(IVec2::ZERO);
                 to_pick_up = Some(PickUp { cursor_offsets });
@@ -228,11 +316,17 @@ pub fn grab_item_system(
     }
 
     if let Some(to_pick_up) = to_pick_up {
+        sounds.write(PlaySound(SoundEffect::Pickup));
         // Pick up all of the items glued to this one.
         for &glue_offset in &to_pick_up.cursor_offsets {
             let glued_item = ground_items.get(&(player_cursor + glue_offset)).unwrap();
 
             let (_, _, mut item) = items.get_mut(glued_item.entity).unwrap();
+            pickup_history.items.push(PickedUpItem {
+                entity: glued_item.entity,
+                position: player_cursor + glue_offset,
+                glued: item.glued.clone(),
+            });
             item.is_held = Some(glue_offset);
             if let Ok(mut wall) = is_wall.get_mut(glued_item.entity) {
                 // Disable the wall while it is being carried.
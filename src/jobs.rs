@@ -0,0 +1,317 @@
+use bevy::prelude::*;
+use serde::Deserialize;
+
+use crate::{
+    billboard::{Billboard, BillboardShadow, Facing},
+    blueprint::{Blueprint, BlueprintRequirements, BlueprintStatus},
+    dialogue::{Dialogue, DialogueState, Npc, OpenDialogue},
+    generator::Challenge,
+    pathfinding::{MoveBehaviour, NpcMover},
+};
+
+/// Where the level's jobs are listed, in the order they are offered.
+pub const JOBS_PATH: &str = "assets/jobs.ron";
+
+pub struct JobsPlugin;
+
+impl Plugin for JobsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Wallet>()
+            .add_message::<JobCompleted>()
+            .add_message::<LevelComplete>()
+            .add_systems(Startup, setup_jobs_system)
+            .add_systems(
+                Update,
+                (
+                    start_job_system,
+                    complete_job_system,
+                    client_leaving_system,
+                    show_wallet_system,
+                )
+                    .chain(),
+            );
+    }
+}
+
+/// A room a client wants built.
+#[derive(Deserialize, Clone, Debug)]
+pub struct JobSpec {
+    /// Set as a dialogue flag, as `<id>_done`, when the job is completed.
+    pub id: String,
+    pub client_image: String,
+    pub client_dialogue: String,
+    pub client_cell: [i32; 2],
+    pub blueprint_cell: [i32; 2],
+    #[serde(default)]
+    pub requirements: BlueprintRequirements,
+    pub payment: u32,
+    /// The client's dialogue node to open when the room is done.
+    pub done_node: String,
+}
+
+impl JobSpec {
+    pub fn done_flag(&self) -> String {
+        format!("{}_done", self.id)
+    }
+}
+
+/// The level's jobs, which are worked through one at a time.
+#[derive(Resource, Default)]
+pub struct Jobs {
+    pub specs: Vec<JobSpec>,
+    /// The index of the job being worked on. Once this reaches the end of `specs`, the level is
+    /// complete.
+    pub current: usize,
+    /// Whether the current job's client and blueprint have been spawned.
+    pub started: bool,
+}
+
+impl Jobs {
+    pub fn load(path: &str) -> Result<Jobs, Box<dyn std::error::Error>> {
+        let text = std::fs::read_to_string(path)?;
+        Ok(Jobs {
+            specs: ron::from_str(&text)?,
+            current: 0,
+            started: false,
+        })
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct Wallet {
+    pub money: u32,
+}
+
+/// A client who has commissioned a room.
+#[derive(Component)]
+pub struct Client {
+    pub job: usize,
+}
+
+/// A blueprint that a client is waiting on.
+#[derive(Component)]
+pub struct Commission {
+    pub job: usize,
+}
+
+/// A client whose room is done. They look around inside it once they have finished talking, and
+/// then leave.
+#[derive(Component)]
+pub struct Leaving {
+    pub elapsed: f32,
+}
+
+#[derive(Message, Copy, Clone, Debug)]
+pub struct JobCompleted {
+    pub job: usize,
+    pub payment: u32,
+}
+
+#[derive(Message, Copy, Clone, Debug)]
+pub struct LevelComplete;
+
+#[derive(Component)]
+pub struct WalletText;
+
+pub fn setup_jobs_system(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    challenge: Option<Res<Challenge>>,
+) {
+    // The jobs are for the level on disk, so generated levels have none.
+    let jobs = match Jobs::load(JOBS_PATH) {
+        _ if challenge.is_some() => Jobs::default(),
+        Ok(jobs) => jobs,
+        Err(err) => {
+            warn!("no jobs for this level: cannot load {}: {}", JOBS_PATH, err);
+            Jobs::default()
+        }
+    };
+    commands.insert_resource(jobs);
+
+    let font = asset_server.load("fonts/delius/Delius-Regular.ttf");
+    commands
+        .spawn(Node {
+            position_type: PositionType::Absolute,
+            width: percent(100),
+            justify_content: JustifyContent::FlexEnd,
+            padding: UiRect::axes(px(20), px(10)),
+            ..default()
+        })
+        .with_children(|builder| {
+            builder.spawn((
+                WalletText,
+                Text::default(),
+                TextColor(Color::linear_rgb(1., 0.9, 0.3)),
+                TextFont {
+                    font,
+                    font_size: 30.0,
+                    ..default()
+                },
+            ));
+        });
+}
+
+/// Brings in the client for the current job, and unlocks their blueprint.
+pub fn start_job_system(
+    mut commands: Commands,
+    mut jobs: ResMut<Jobs>,
+    blueprints: Query<(Entity, &Transform), With<Blueprint>>,
+) {
+    if jobs.started || jobs.current >= jobs.specs.len() {
+        return;
+    }
+    jobs.started = true;
+    let job = jobs.current;
+    let spec = &jobs.specs[job];
+
+    let [x, y] = spec.client_cell;
+    let mut client = commands.spawn((
+        Client { job },
+        Billboard {
+            image: spec.client_image.clone(),
+            shadow: BillboardShadow::Cast,
+            ..default()
+        },
+        Facing::default(),
+        Transform::from_xyz(x as f32, 0., y as f32),
+    ));
+    // Without their dialogue the client cannot be talked to, but the job can still be done.
+    match Dialogue::load(&spec.client_dialogue) {
+        Ok(dialogue) => {
+            client.insert(Npc { dialogue });
+        }
+        Err(err) => warn!("cannot load {}: {}", spec.client_dialogue, err),
+    }
+
+    // Use the blueprint already in the level at this cell, or make a new one.
+    let blueprint_cell = IVec2::from(spec.blueprint_cell);
+    let existing = blueprints.iter().find(|(_, blueprint_transform)| {
+        blueprint_transform.translation.xz().round().as_ivec2() == blueprint_cell
+    });
+    let blueprint_entity = match existing {
+        Some((blueprint_entity, _)) => blueprint_entity,
+        None => commands
+            .spawn((
+                Blueprint,
+                Billboard {
+                    image: "blueprint.png".to_string(),
+                    ..default()
+                },
+                Transform::from_xyz(blueprint_cell.x as f32, 0., blueprint_cell.y as f32),
+            ))
+            .id(),
+    };
+    commands
+        .entity(blueprint_entity)
+        .insert((Commission { job }, spec.requirements.clone()));
+}
+
+/// Pays for the current job once its blueprint is valid, and moves on to the next one.
+pub fn complete_job_system(
+    mut commands: Commands,
+    mut jobs: ResMut<Jobs>,
+    mut wallet: ResMut<Wallet>,
+    mut dialogue_state: ResMut<DialogueState>,
+    commissions: Query<(Entity, &Commission, &BlueprintStatus)>,
+    clients: Query<(Entity, &Client, Has<Npc>), Without<Leaving>>,
+    mut job_completed: MessageWriter<JobCompleted>,
+    mut level_complete: MessageWriter<LevelComplete>,
+) {
+    for (blueprint_entity, commission, status) in commissions.iter() {
+        if commission.job != jobs.current || !status.is_valid() {
+            continue;
+        }
+        let spec = &jobs.specs[commission.job];
+        commands.entity(blueprint_entity).remove::<Commission>();
+        wallet.money += spec.payment;
+        job_completed.write(JobCompleted {
+            job: commission.job,
+            payment: spec.payment,
+        });
+        dialogue_state.flags.insert(spec.done_flag());
+
+        for (client_entity, client, can_talk) in clients.iter() {
+            if client.job != commission.job {
+                continue;
+            }
+            // The client thanks the player before leaving.
+            if can_talk {
+                dialogue_state.open = Some(OpenDialogue {
+                    npc: client_entity,
+                    node: spec.done_node.clone(),
+                    line: 0,
+                    choice: 0,
+                });
+            }
+            commands.entity(client_entity).insert((
+                Leaving { elapsed: 0. },
+                NpcMover::new(MoveBehaviour::GoToDoor {
+                    blueprint: blueprint_entity,
+                }),
+            ));
+        }
+
+        jobs.current += 1;
+        jobs.started = false;
+        if jobs.current == jobs.specs.len() {
+            level_complete.write(LevelComplete);
+        }
+        break;
+    }
+}
+
+/// How long a client looks around their new room before leaving.
+const INSPECT_SECONDS: f32 = 5.;
+const LEAVING_SECONDS: f32 = 0.75;
+
+/// Clients float away once they are done talking and have looked around their room.
+pub fn client_leaving_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    dialogue_state: Res<DialogueState>,
+    mut clients: Query<(Entity, &mut Transform, &mut Leaving, Option<&NpcMover>)>,
+) {
+    for (client_entity, mut client_transform, mut leaving, mover) in clients.iter_mut() {
+        let is_talking = dialogue_state
+            .open
+            .as_ref()
+            .is_some_and(|open| open.npc == client_entity);
+        let is_walking_in = mover.is_some_and(|mover| !mover.is_inside_room());
+        if is_talking || is_walking_in {
+            continue;
+        }
+        leaving.elapsed += time.delta_secs();
+        let t = (leaving.elapsed - INSPECT_SECONDS) / LEAVING_SECONDS;
+        if t < 0. {
+            continue;
+        }
+        if t >= 1. {
+            commands.entity(client_entity).despawn();
+            continue;
+        }
+        client_transform.translation.y += time.delta_secs() * 2.;
+        client_transform.scale = Vec3::splat(1. - t);
+    }
+}
+
+pub fn show_wallet_system(
+    jobs: Res<Jobs>,
+    wallet: Res<Wallet>,
+    mut wallet_text: Query<&mut Text, With<WalletText>>,
+) {
+    if !jobs.is_changed() && !wallet.is_changed() {
+        return;
+    }
+    let Ok(mut text) = wallet_text.single_mut() else {
+        return;
+    };
+    let progress = if jobs.specs.is_empty() {
+        String::new()
+    } else if jobs.current >= jobs.specs.len() {
+        "Level complete!".to_string()
+    } else {
+        format!("Job {} of {}", jobs.current + 1, jobs.specs.len())
+    };
+    text.0 = format!("{}   ${}", progress, wallet.money);
+}
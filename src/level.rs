@@ -0,0 +1,289 @@
+use bevy::{platform::collections::HashSet, prelude::*};
+
+use crate::{
+    blueprint::BlueprintRequirements,
+    level_text::{parse_level_text, write_level_text},
+};
+
+pub type LevelColor = [u8; 3];
+pub const COLOR_PLAYER: LevelColor = [255, 0, 0];
+pub const COLOR_FLOOR: LevelColor = [255, 255, 255];
+pub const COLOR_WATER: LevelColor = [128, 128, 255];
+pub const COLOR_WALL: LevelColor = [128, 128, 128];
+
+pub const COLOR_FENCE: LevelColor = [255, 64, 0];
+pub const COLOR_BRIDGE: LevelColor = [128, 64, 0];
+pub const COLOR_DOOR: LevelColor = [255, 128, 0];
+pub const COLOR_BLUEPRINT: LevelColor = [0, 0, 255];
+pub const COLOR_BRICK_WALL: LevelColor = [255, 60, 0];
+
+/// What a single pixel of level.png describes.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum LevelTile {
+    Floor,
+    Water,
+    Wall,
+    Player,
+    Door,
+    Blueprint,
+    BrickWall,
+    Fence,
+    Bridge,
+    /// A pixel with a color that doesn't mean anything. It is treated as floor.
+    Unknown(LevelColor),
+}
+
+impl LevelTile {
+    /// Every tile with a color of its own.
+    pub const ALL: [LevelTile; 9] = [
+        LevelTile::Floor,
+        LevelTile::Water,
+        LevelTile::Wall,
+        LevelTile::Player,
+        LevelTile::Door,
+        LevelTile::Blueprint,
+        LevelTile::BrickWall,
+        LevelTile::Fence,
+        LevelTile::Bridge,
+    ];
+
+    pub fn from_color(color: LevelColor) -> LevelTile {
+        match color {
+            COLOR_PLAYER => LevelTile::Player,
+            COLOR_FLOOR => LevelTile::Floor,
+            COLOR_WATER => LevelTile::Water,
+            COLOR_WALL => LevelTile::Wall,
+            COLOR_FENCE => LevelTile::Fence,
+            COLOR_BRIDGE => LevelTile::Bridge,
+            COLOR_DOOR => LevelTile::Door,
+            COLOR_BLUEPRINT => LevelTile::Blueprint,
+            COLOR_BRICK_WALL => LevelTile::BrickWall,
+            _ => LevelTile::Unknown(color),
+        }
+    }
+
+    /// Whether the tile is an item the player can pick up.
+    pub fn is_item(self) -> bool {
+        matches!(
+            self,
+            LevelTile::BrickWall | LevelTile::Fence | LevelTile::Bridge
+        )
+    }
+
+    /// Whether an item of this tile blocks movement and closes off rooms, like a wall.
+    pub fn is_wall_item(self) -> bool {
+        matches!(self, LevelTile::BrickWall | LevelTile::Fence)
+    }
+
+    pub fn color(self) -> LevelColor {
+        match self {
+            LevelTile::Player => COLOR_PLAYER,
+            LevelTile::Floor => COLOR_FLOOR,
+            LevelTile::Water => COLOR_WATER,
+            LevelTile::Wall => COLOR_WALL,
+            LevelTile::Fence => COLOR_FENCE,
+            LevelTile::Bridge => COLOR_BRIDGE,
+            LevelTile::Door => COLOR_DOOR,
+            LevelTile::Blueprint => COLOR_BLUEPRINT,
+            LevelTile::BrickWall => COLOR_BRICK_WALL,
+            LevelTile::Unknown(color) => color,
+        }
+    }
+}
+
+/// An item described by a level pixel.
+#[derive(Clone, Debug)]
+pub struct LevelItem {
+    pub cell: IVec2,
+    pub tile: LevelTile,
+    /// The offsets from `cell` to every other item in its group.
+    pub glued: Vec<IVec2>,
+}
+
+/// The level the game loads, unless there is a [`LEVEL_TEXT_PATH`].
+pub const LEVEL_PATH: &str = "assets/level.png";
+
+/// Where the editor saves the level, as level text, which keeps everything the level image can't.
+/// The game loads it instead of [`LEVEL_PATH`] when it exists.
+pub const LEVEL_TEXT_PATH: &str = "assets/level.txt";
+
+/// The path of the level the game loads.
+pub fn game_level_path() -> &'static str {
+    if std::path::Path::new(LEVEL_TEXT_PATH).exists() {
+        LEVEL_TEXT_PATH
+    } else {
+        LEVEL_PATH
+    }
+}
+
+/// The most items a glue group can hold, which is how many markers there are to show where held
+/// items will be put down.
+pub const MAX_GLUE_GROUP: usize = 10;
+
+/// The tile grid described by a level image or level text, with x along the image width and y
+/// along its height.
+#[derive(Resource, Clone)]
+pub struct Level {
+    pub width: u32,
+    pub height: u32,
+    pub tiles: Vec<LevelTile>,
+    /// Groups of item cells which are glued together, and picked up as a whole.
+    pub glue: Vec<Vec<IVec2>>,
+    /// What the room around each blueprint needs, for blueprints which need more than a closed
+    /// room with a door.
+    pub blueprints: Vec<(IVec2, BlueprintRequirements)>,
+}
+
+impl Level {
+    /// Loads level text from `.txt` files, and a level image from anything else.
+    pub fn load(path: &str) -> Result<Level, Box<dyn std::error::Error>> {
+        if path.ends_with(".txt") {
+            let text = std::fs::read_to_string(path)?;
+            return Ok(parse_level_text(&text)?);
+        }
+        let level = image::open(path)?;
+        Ok(Level::from_image(&level.to_rgb8()))
+    }
+
+    /// Saves level text to `.txt` files, and a level image to anything else.
+    pub fn save(&self, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+        if path.ends_with(".txt") {
+            let text = write_level_text(self);
+            // There are only so many characters for colors, so check the text reads back first.
+            parse_level_text(&text)
+                .map_err(|err| format!("the level can't be written as level text: {}", err))?;
+            std::fs::write(path, text)?;
+        } else {
+            self.to_image().save(path)?;
+        }
+        Ok(())
+    }
+
+    /// Orthogonally adjacent fence and bridge pixels are glued together into one group. Brick walls
+    /// are never glued.
+    pub fn from_image(image: &image::RgbImage) -> Level {
+        let mut tiles = Vec::with_capacity((image.width() * image.height()) as usize);
+        for y in 0..image.height() {
+            for x in 0..image.width() {
+                tiles.push(LevelTile::from_color(image[(x, y)].0));
+            }
+        }
+        let mut level = Level {
+            width: image.width(),
+            height: image.height(),
+            tiles,
+            glue: Vec::new(),
+            blueprints: Vec::new(),
+        };
+        level.glue = level.adjacent_glue();
+        level
+    }
+
+    /// The level image, which keeps everything but the glue groups and blueprint requirements.
+    pub fn to_image(&self) -> image::RgbImage {
+        image::RgbImage::from_fn(self.width, self.height, |x, y| {
+            image::Rgb(self.get(IVec2::new(x as i32, y as i32)).unwrap().color())
+        })
+    }
+
+    pub fn get(&self, cell: IVec2) -> Option<LevelTile> {
+        if cell.x < 0 || cell.y < 0 || cell.x >= self.width as i32 || cell.y >= self.height as i32 {
+            return None;
+        }
+        Some(self.tiles[(cell.y as u32 * self.width + cell.x as u32) as usize])
+    }
+
+    /// Changes the tile at `cell`, if it is in the level.
+    pub fn set(&mut self, cell: IVec2, tile: LevelTile) {
+        if self.get(cell).is_some() {
+            self.tiles[(cell.y as u32 * self.width + cell.x as u32) as usize] = tile;
+        }
+    }
+
+    pub fn bounds(&self) -> LevelBounds {
+        LevelBounds {
+            min: Vec2::splat(-0.5),
+            max: Vec2::new(self.width as f32, self.height as f32) - 0.5,
+        }
+    }
+
+    /// All cells of the level, column by column.
+    pub fn cells(&self) -> impl Iterator<Item = (IVec2, LevelTile)> + '_ {
+        (0..self.width as i32).flat_map(move |x| {
+            (0..self.height as i32).map(move |y| {
+                let cell = IVec2::new(x, y);
+                (cell, self.get(cell).unwrap())
+            })
+        })
+    }
+
+    /// The glue groups a level image describes, made of orthogonally adjacent fence and bridge
+    /// pixels.
+    pub fn adjacent_glue(&self) -> Vec<Vec<IVec2>> {
+        let is_glued = |tile: LevelTile| matches!(tile, LevelTile::Fence | LevelTile::Bridge);
+        let mut glue = Vec::new();
+        let mut grouped: HashSet<IVec2> = HashSet::new();
+        for (cell, tile) in self.cells() {
+            if !is_glued(tile) || grouped.contains(&cell) {
+                continue;
+            }
+            let mut group = vec![cell];
+            grouped.insert(cell);
+            let mut index = 0;
+            while index < group.len() {
+                let current = group[index];
+                index += 1;
+                for dir in [IVec2::X, IVec2::Y, IVec2::NEG_X, IVec2::NEG_Y] {
+                    let neighbor = current + dir;
+                    if self.get(neighbor).is_some_and(is_glued) && grouped.insert(neighbor) {
+                        group.push(neighbor);
+                    }
+                }
+            }
+            if group.len() > 1 {
+                glue.push(group);
+            }
+        }
+        glue
+    }
+
+    /// The offsets from `cell` to the rest of its glue group.
+    pub fn glued(&self, cell: IVec2) -> Vec<IVec2> {
+        self.glue
+            .iter()
+            .find(|group| group.contains(&cell))
+            .into_iter()
+            .flatten()
+            .filter(|&&other| other != cell)
+            .map(|&other| other - cell)
+            .collect()
+    }
+
+    /// The level's items, each with the offsets to the rest of its glue group.
+    pub fn items(&self) -> Vec<LevelItem> {
+        self.cells()
+            .filter(|(_, tile)| tile.is_item())
+            .map(|(cell, tile)| LevelItem {
+                cell,
+                tile,
+                glued: self.glued(cell),
+            })
+            .collect()
+    }
+
+    /// What the room around the blueprint at `cell` needs.
+    pub fn requirements(&self, cell: IVec2) -> BlueprintRequirements {
+        self.blueprints
+            .iter()
+            .find(|(location, _)| *location == cell)
+            .map(|(_, requirements)| requirements.clone())
+            .unwrap_or_default()
+    }
+}
+
+/// The rectangle covered by the level's tiles, in world x and z.
+#[derive(Resource, Copy, Clone, Debug)]
+pub struct LevelBounds {
+    pub min: Vec2,
+    pub max: Vec2,
+}
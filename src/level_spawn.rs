@@ -0,0 +1,155 @@
+use bevy::prelude::*;
+
+use crate::{
+    billboard::{Billboard, BillboardShadow, Facing},
+    blueprint::{Blueprint, Door},
+    camera::FadeWhenOccluding,
+    item::Item,
+    level::{Level, LevelTile},
+    player::{Player, Wall, player_animation},
+};
+
+/// Marks everything spawned from a level cell, so the cell or the whole level can be spawned again.
+#[derive(Component)]
+pub struct LevelEntity {
+    /// The cell the entity was spawned from. Items keep it after they are moved.
+    pub cell: IVec2,
+}
+
+/// The meshes and materials shared by the entities spawned from the level.
+#[derive(Resource)]
+pub struct LevelAssets {
+    pub wall_mesh: Handle<Mesh>,
+    pub wall_material: Handle<StandardMaterial>,
+    pub bridge_mesh: Handle<Mesh>,
+    pub bridge_material: Handle<StandardMaterial>,
+}
+
+impl LevelAssets {
+    pub fn new(meshes: &mut Assets<Mesh>, materials: &mut Assets<StandardMaterial>) -> LevelAssets {
+        LevelAssets {
+            wall_mesh: meshes.add(Cuboid::default()),
+            wall_material: materials.add(StandardMaterial {
+                base_color: Color::linear_rgb(1., 0.5, 0.2),
+                perceptual_roughness: 1.,
+                ..default()
+            }),
+            bridge_mesh: meshes.add(Cuboid::new(0.9, 0.1, 0.9)),
+            bridge_material: materials.add(StandardMaterial {
+                base_color: Color::linear_rgb(0.9, 0.8, 0.5),
+                ..default()
+            }),
+        }
+    }
+}
+
+/// Spawns the player, walls, doors, blueprints and items for every cell of the level.
+pub fn spawn_level(commands: &mut Commands, level: &Level, assets: &LevelAssets) {
+    for (cell, _) in level.cells() {
+        spawn_level_cell(commands, level, assets, cell);
+    }
+}
+
+/// Spawns whatever the level has at `cell`.
+pub fn spawn_level_cell(commands: &mut Commands, level: &Level, assets: &LevelAssets, cell: IVec2) {
+    let Some(tile) = level.get(cell) else {
+        return;
+    };
+    let at = Vec3::new(cell.x as f32, 0., cell.y as f32);
+    let level_entity = LevelEntity { cell };
+
+    match tile {
+        LevelTile::Player => {
+            commands.spawn((
+                level_entity,
+                avian3d::prelude::RigidBody::Dynamic,
+                avian3d::prelude::Collider::capsule(0.3, 0.8),
+                avian3d::prelude::LockedAxes::ROTATION_LOCKED,
+                Player {
+                    velocity: Vec3::ZERO,
+                    recent_velocity: Vec3::ZERO,
+                    cursor: Vec3::new(1., 0., 0.),
+                    last_safe_cell: cell,
+                    in_water: false,
+                },
+                Billboard {
+                    image: "duck_realtor.png".to_string(),
+                    anchor: Billboard::ANCHOR_CENTER,
+                    shadow: BillboardShadow::Cast,
+                    ..default()
+                },
+                player_animation(),
+                Facing::default(),
+                Transform::from_translation(at + Vec3::new(0.0, 3.9, 0.0)),
+            ));
+        }
+        LevelTile::Door => {
+            commands.spawn((
+                level_entity,
+                Billboard {
+                    image: "door.png".to_string(),
+                    shadow: BillboardShadow::Cast,
+                    ..default()
+                },
+                Transform::from_translation(at),
+                Door,
+            ));
+        }
+        LevelTile::Blueprint => {
+            commands.spawn((
+                level_entity,
+                Billboard {
+                    image: "blueprint.png".to_string(),
+                    ..default()
+                },
+                Blueprint,
+                level.requirements(cell),
+                Transform::from_translation(at),
+            ));
+        }
+        LevelTile::Wall => {
+            commands.spawn((
+                level_entity,
+                Wall { enabled: true },
+                FadeWhenOccluding,
+                Mesh3d(assets.wall_mesh.clone()),
+                MeshMaterial3d(assets.wall_material.clone()),
+                Transform::from_translation(at + Vec3::new(0., 0.5, 0.)),
+            ));
+        }
+        tile if tile.is_item() => {
+            let mut item = commands.spawn((
+                level_entity,
+                Item {
+                    glued: level.glued(cell),
+                    is_held: None,
+                },
+                Transform::from_translation(at),
+            ));
+            match tile {
+                LevelTile::Bridge => {
+                    item.insert((
+                        Mesh3d(assets.bridge_mesh.clone()),
+                        MeshMaterial3d(assets.bridge_material.clone()),
+                    ));
+                }
+                tile => {
+                    let image = if tile == LevelTile::Fence {
+                        "fence.png"
+                    } else {
+                        "brick_wall.png"
+                    };
+                    item.insert(Billboard {
+                        image: image.to_string(),
+                        shadow: BillboardShadow::Cast,
+                        ..default()
+                    });
+                }
+            }
+            if tile.is_wall_item() {
+                item.insert(Wall { enabled: true });
+            }
+        }
+        _ => {}
+    }
+}
@@ -0,0 +1,327 @@
+//! A plain-text level format, which is easier to review in diffs than a level image.
+//!
+//! ```text
+//! [legend]
+//! . Floor
+//! # Wall
+//! @ Player
+//! f Fence
+//! B Blueprint
+//! D Door
+//! x #fa3e03
+//! [tiles]
+//! #####
+//! #B.@#
+//! #ff.#
+//! ###D#
+//! [glue]
+//! (1, 2), (2, 2)
+//! [blueprints]
+//! (1, 1), (min_size: 2)
+//! ```
+//!
+//! Each legend line gives a tile character, and either a tile name or a `#rrggbb` color. Each glue
+//! line lists the cells of one glue group, and each blueprint line gives a blueprint's cell and
+//! what its room needs. Empty lines are ignored.
+
+use bevy::{platform::collections::HashMap, prelude::*};
+
+use crate::{
+    blueprint::BlueprintRequirements,
+    level::{Level, LevelColor, LevelTile},
+};
+
+/// The character each tile is written with, unless the legend says otherwise.
+pub const DEFAULT_LEGEND: [(char, LevelTile); 9] = [
+    ('.', LevelTile::Floor),
+    ('~', LevelTile::Water),
+    ('#', LevelTile::Wall),
+    ('@', LevelTile::Player),
+    ('D', LevelTile::Door),
+    ('B', LevelTile::Blueprint),
+    ('b', LevelTile::BrickWall),
+    ('f', LevelTile::Fence),
+    ('=', LevelTile::Bridge),
+];
+
+/// Characters for colors which don't have a tile of their own.
+const SPARE_CHARACTERS: &str = "0123456789ACEFGHIJKLMNOPQRSTUVWXYZacdeghijklmnopqrstuvwxyz";
+
+#[derive(Debug)]
+pub struct LevelTextError {
+    /// Counting from 1.
+    pub line: usize,
+    pub message: String,
+}
+
+impl std::fmt::Display for LevelTextError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for LevelTextError {}
+
+#[derive(Copy, Clone, Eq, PartialEq)]
+enum Section {
+    Legend,
+    Tiles,
+    Glue,
+    Blueprints,
+}
+
+fn parse_color(text: &str) -> Option<LevelColor> {
+    let hex = text.strip_prefix('#').filter(|hex| hex.len() == 6)?;
+    let channel = |index: usize| u8::from_str_radix(hex.get(index..index + 2)?, 16).ok();
+    Some([channel(0)?, channel(2)?, channel(4)?])
+}
+
+fn parse_legend_tile(text: &str) -> Option<LevelTile> {
+    if let Some(color) = parse_color(text) {
+        return Some(LevelTile::from_color(color));
+    }
+    LevelTile::ALL
+        .into_iter()
+        .find(|tile| format!("{:?}", tile) == text)
+}
+
+fn legend_tile_name(tile: LevelTile) -> String {
+    match tile {
+        LevelTile::Unknown([r, g, b]) => format!("#{:02x}{:02x}{:02x}", r, g, b),
+        tile => format!("{:?}", tile),
+    }
+}
+
+/// Parses level text into the same level an equivalent level image loads as.
+pub fn parse_level_text(text: &str) -> Result<Level, LevelTextError> {
+    let mut section = None;
+    let mut legend: HashMap<char, LevelTile> = HashMap::new();
+    let mut rows: Vec<(usize, Vec<char>)> = Vec::new();
+    let mut glue: Vec<Vec<IVec2>> = Vec::new();
+    let mut blueprints: Vec<(IVec2, BlueprintRequirements)> = Vec::new();
+
+    for (index, line) in text.lines().enumerate() {
+        let line_number = index + 1;
+        let error = |message: String| LevelTextError {
+            line: line_number,
+            message,
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        match line.trim() {
+            "[legend]" => section = Some(Section::Legend),
+            "[tiles]" => section = Some(Section::Tiles),
+            "[glue]" => section = Some(Section::Glue),
+            "[blueprints]" => section = Some(Section::Blueprints),
+            _ => match section {
+                None => return Err(error("expected a section, like [tiles]".to_string())),
+                Some(Section::Legend) => {
+                    let mut chars = line.chars();
+                    let symbol = chars.next().unwrap();
+                    let name = chars.as_str().trim();
+                    let Some(tile) = parse_legend_tile(name) else {
+                        return Err(error(format!(
+                            "unknown tile {:?}, expected a tile name or a #rrggbb color",
+                            name
+                        )));
+                    };
+                    if legend.insert(symbol, tile).is_some() {
+                        return Err(error(format!("{:?} is in the legend twice", symbol)));
+                    }
+                }
+                Some(Section::Tiles) => rows.push((line_number, line.chars().collect())),
+                Some(Section::Glue) => {
+                    let group: Vec<(i32, i32)> = ron::from_str(&format!("[{}]", line))
+                        .map_err(|err| error(format!("expected cells, like (1, 2): {}", err)))?;
+                    glue.push(group.into_iter().map(IVec2::from).collect());
+                }
+                Some(Section::Blueprints) => {
+                    let (cell, requirements): ((i32, i32), BlueprintRequirements) =
+                        ron::from_str(&format!("({})", line)).map_err(|err| {
+                            error(format!(
+                                "expected a cell and requirements, like (1, 2), (min_size: 4): {}",
+                                err
+                            ))
+                        })?;
+                    blueprints.push((IVec2::from(cell), requirements));
+                }
+            },
+        }
+    }
+
+    let width = rows.first().map_or(0, |(_, row)| row.len());
+    let mut tiles = Vec::with_capacity(width * rows.len());
+    for (line_number, row) in &rows {
+        if row.len() != width {
+            return Err(LevelTextError {
+                line: *line_number,
+                message: format!("the row is {} tiles wide, not {}", row.len(), width),
+            });
+        }
+        for symbol in row {
+            let tile = legend.get(symbol).copied().or_else(|| {
+                DEFAULT_LEGEND
+                    .iter()
+                    .find(|(default, _)| default == symbol)
+                    .map(|&(_, tile)| tile)
+            });
+            let Some(tile) = tile else {
+                return Err(LevelTextError {
+                    line: *line_number,
+                    message: format!("{:?} isn't in the legend", symbol),
+                });
+            };
+            tiles.push(tile);
+        }
+    }
+
+    Ok(Level {
+        width: width as u32,
+        height: rows.len() as u32,
+        tiles,
+        glue,
+        blueprints,
+    })
+}
+
+/// Writes a level as level text, with a legend for every tile it uses.
+pub fn write_level_text(level: &Level) -> String {
+    let mut legend: Vec<(char, LevelTile)> = Vec::new();
+    let mut spare = SPARE_CHARACTERS
+        .chars()
+        .filter(|spare| !DEFAULT_LEGEND.iter().any(|(symbol, _)| symbol == spare));
+    for y in 0..level.height as i32 {
+        for x in 0..level.width as i32 {
+            let tile = level.get(IVec2::new(x, y)).unwrap();
+            if legend.iter().any(|&(_, known)| known == tile) {
+                continue;
+            }
+            let symbol = DEFAULT_LEGEND
+                .iter()
+                .find(|&&(_, default)| default == tile)
+                .map(|&(symbol, _)| symbol)
+                .or_else(|| spare.next())
+                .unwrap_or('?');
+            legend.push((symbol, tile));
+        }
+    }
+    legend.sort_by_key(|&(symbol, _)| symbol);
+
+    let mut text = String::from("[legend]\n");
+    for &(symbol, tile) in &legend {
+        text += &format!("{} {}\n", symbol, legend_tile_name(tile));
+    }
+
+    text += "[tiles]\n";
+    for y in 0..level.height as i32 {
+        for x in 0..level.width as i32 {
+            let tile = level.get(IVec2::new(x, y)).unwrap();
+            let &(symbol, _) = legend.iter().find(|&&(_, known)| known == tile).unwrap();
+            text.push(symbol);
+        }
+        text.push('\n');
+    }
+
+    if !level.glue.is_empty() {
+        text += "[glue]\n";
+        for group in &level.glue {
+            let cells: Vec<String> = group
+                .iter()
+                .map(|cell| format!("({}, {})", cell.x, cell.y))
+                .collect();
+            text += &format!("{}\n", cells.join(", "));
+        }
+    }
+
+    if !level.blueprints.is_empty() {
+        text += "[blueprints]\n";
+        for (cell, requirements) in &level.blueprints {
+            text += &format!(
+                "({}, {}), {}\n",
+                cell.x,
+                cell.y,
+                ron::to_string(requirements).unwrap()
+            );
+        }
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIXTURE: &str = "[legend]
+. Floor
+# Wall
+@ Player
+f Fence
+B Blueprint
+D Door
+x #fa3e03
+[tiles]
+#####
+#B.@#
+#ffx#
+###D#
+[glue]
+(1, 2), (2, 2)
+[blueprints]
+(1, 1), (min_size: 2)
+";
+
+    #[test]
+    fn parses_tiles_glue_and_blueprints() {
+        let level = parse_level_text(FIXTURE).unwrap();
+        assert_eq!((level.width, level.height), (5, 4));
+        assert_eq!(level.get(IVec2::new(3, 1)), Some(LevelTile::Player));
+        assert_eq!(
+            level.get(IVec2::new(3, 2)),
+            Some(LevelTile::Unknown([0xfa, 0x3e, 0x03]))
+        );
+        assert_eq!(level.glue, vec![vec![IVec2::new(1, 2), IVec2::new(2, 2)]]);
+        assert_eq!(level.requirements(IVec2::new(1, 1)).min_size, 2);
+    }
+
+    #[test]
+    fn round_trips_through_text() {
+        let level = parse_level_text(FIXTURE).unwrap();
+        let written = parse_level_text(&write_level_text(&level)).unwrap();
+        assert_eq!(written.tiles, level.tiles);
+        assert_eq!(written.glue, level.glue);
+        assert_eq!(written.blueprints, level.blueprints);
+    }
+
+    #[test]
+    fn matches_the_level_image() {
+        let level = parse_level_text(FIXTURE).unwrap();
+        let from_image = Level::from_image(&level.to_image());
+        assert_eq!(from_image.tiles, level.tiles);
+        assert_eq!(from_image.glue, level.glue);
+    }
+
+    #[test]
+    fn too_many_colors_are_not_saved() {
+        let tiles: Vec<LevelTile> = (0..100)
+            .map(|red| LevelTile::Unknown([red, 7, 7]))
+            .collect();
+        let level = Level {
+            width: tiles.len() as u32,
+            height: 1,
+            tiles,
+            glue: Vec::new(),
+            blueprints: Vec::new(),
+        };
+        let path = std::env::temp_dir().join("too_many_colors_are_not_saved.txt");
+        let path = path.to_str().unwrap();
+        assert!(level.save(path).is_err());
+        assert!(!std::path::Path::new(path).exists());
+    }
+
+    #[test]
+    fn reports_the_line_of_a_ragged_row() {
+        let err = parse_level_text("[tiles]\n###\n##\n").err().unwrap();
+        assert_eq!(err.line, 3);
+    }
+}
@@ -0,0 +1,24 @@
+pub mod audio;
+pub mod billboard;
+pub mod blueprint;
+pub mod camera;
+pub mod controls;
+pub mod dialogue;
+#[cfg(feature = "editor")]
+pub mod editor;
+pub mod export;
+pub mod generator;
+pub mod ground;
+pub mod item;
+pub mod jobs;
+pub mod level;
+pub mod level_spawn;
+pub mod level_text;
+pub mod lint;
+pub mod overlay;
+pub mod pathfinding;
+pub mod player;
+pub mod rooms;
+pub mod settings;
+pub mod solver;
+pub mod tutorial;
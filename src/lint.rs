@@ -0,0 +1,336 @@
+//! Checks for mistakes in level and room images which would otherwise only show up at runtime.
+
+use std::collections::VecDeque;
+
+use bevy::{platform::collections::HashSet, prelude::*};
+
+use crate::level::{Level, LevelColor, LevelTile, MAX_GLUE_GROUP};
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+/// A problem found in an image, at the pixel it is about, if there is one.
+#[derive(Clone, Debug)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub pixel: Option<IVec2>,
+    pub message: String,
+}
+
+impl Diagnostic {
+    fn error(pixel: Option<IVec2>, message: String) -> Diagnostic {
+        Diagnostic {
+            severity: Severity::Error,
+            pixel,
+            message,
+        }
+    }
+
+    fn warning(pixel: Option<IVec2>, message: String) -> Diagnostic {
+        Diagnostic {
+            severity: Severity::Warning,
+            pixel,
+            message,
+        }
+    }
+}
+
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let severity = match self.severity {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        match self.pixel {
+            Some(pixel) => write!(
+                f,
+                "{} at ({}, {}): {}",
+                severity, pixel.x, pixel.y, self.message
+            ),
+            None => write!(f, "{}: {}", severity, self.message),
+        }
+    }
+}
+
+fn color_name(color: LevelColor) -> String {
+    format!("#{:02x}{:02x}{:02x}", color[0], color[1], color[2])
+}
+
+/// The known tile whose color is closest to `color`.
+fn closest_tile(color: LevelColor) -> LevelTile {
+    let distance = |tile: &LevelTile| -> i32 {
+        tile.color()
+            .iter()
+            .zip(color)
+            .map(|(&a, b)| (a as i32 - b as i32).pow(2))
+            .sum()
+    };
+    *LevelTile::ALL
+        .iter()
+        .min_by_key(|tile| distance(tile))
+        .unwrap()
+}
+
+/// The doors a room around the blueprint at `location` could ever have. Items can be moved, so
+/// only the level's walls and water bound the search.
+fn possible_doors(level: &Level, location: IVec2) -> HashSet<IVec2> {
+    let mut doors = HashSet::new();
+    let mut reached = HashSet::new();
+    let mut queue = VecDeque::new();
+    reached.insert(location);
+    queue.push_back(location);
+    while let Some(current) = queue.pop_front() {
+        for dir in [IVec2::X, IVec2::Y, IVec2::NEG_X, IVec2::NEG_Y] {
+            let neighbor = current + dir;
+            match level.get(neighbor) {
+                None | Some(LevelTile::Wall | LevelTile::Water) => {}
+                Some(LevelTile::Door) => {
+                    doors.insert(neighbor);
+                }
+                Some(_) => {
+                    if reached.insert(neighbor) {
+                        queue.push_back(neighbor);
+                    }
+                }
+            }
+        }
+    }
+    doors
+}
+
+/// Checks a level for missing or extra players, unknown colors, blueprints which can never have a
+/// door, glue groups with empty cells, and requirements for missing blueprints.
+pub fn lint_level(level: &Level) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+
+    let players: Vec<IVec2> = level
+        .cells()
+        .filter(|&(_, tile)| tile == LevelTile::Player)
+        .map(|(cell, _)| cell)
+        .collect();
+    match players.as_slice() {
+        [] => diagnostics.push(Diagnostic::error(
+            None,
+            format!(
+                "no player pixel ({})",
+                color_name(LevelTile::Player.color())
+            ),
+        )),
+        [_] => {}
+        [first, rest @ ..] => {
+            for player in rest {
+                diagnostics.push(Diagnostic::error(
+                    Some(*player),
+                    format!(
+                        "another player pixel, after the one at ({}, {})",
+                        first.x, first.y
+                    ),
+                ));
+            }
+        }
+    }
+
+    for (cell, tile) in level.cells() {
+        match tile {
+            LevelTile::Unknown(color) => {
+                diagnostics.push(Diagnostic::error(
+                    Some(cell),
+                    format!(
+                        "unknown color {}, treated as floor (the closest is {:?}, {})",
+                        color_name(color),
+                        closest_tile(color),
+                        color_name(closest_tile(color).color()),
+                    ),
+                ));
+            }
+            LevelTile::Blueprint if possible_doors(level, cell).is_empty() => {
+                diagnostics.push(Diagnostic::error(
+                    Some(cell),
+                    "blueprint can never have a door: no door can border its room".to_string(),
+                ));
+            }
+            _ => {}
+        }
+    }
+
+    for group in &level.glue {
+        if group.len() > MAX_GLUE_GROUP {
+            diagnostics.push(Diagnostic::error(
+                group.first().copied(),
+                format!(
+                    "a glue group has {} items, but a group can hold at most {}",
+                    group.len(),
+                    MAX_GLUE_GROUP
+                ),
+            ));
+        }
+        for &cell in group {
+            if !level.get(cell).is_some_and(|tile| tile.is_item()) {
+                diagnostics.push(Diagnostic::error(
+                    Some(cell),
+                    "a glue group includes this cell, which has no item".to_string(),
+                ));
+            }
+        }
+    }
+
+    for (cell, _) in &level.blueprints {
+        if level.get(*cell) != Some(LevelTile::Blueprint) {
+            diagnostics.push(Diagnostic::error(
+                Some(*cell),
+                "there are requirements for a blueprint here, but there is no blueprint"
+                    .to_string(),
+            ));
+        }
+    }
+
+    diagnostics
+}
+
+/// Checks that a room image pair can be loaded, and lines up.
+pub fn lint_room(
+    room_image: &image::DynamicImage,
+    room_collider_image: &image::DynamicImage,
+) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    for (name, layer) in [("room", room_image), ("room collider", room_collider_image)] {
+        if !matches!(layer, image::DynamicImage::ImageRgba8(_)) {
+            diagnostics.push(Diagnostic::error(
+                None,
+                format!(
+                    "the {} image is {:?}, but it must be rgba8",
+                    name,
+                    layer.color()
+                ),
+            ));
+        }
+    }
+    if room_image.width() != room_collider_image.width()
+        || room_image.height() != room_collider_image.height()
+    {
+        diagnostics.push(Diagnostic::error(
+            None,
+            format!(
+                "the room image is {}x{}, but its collider image is {}x{}",
+                room_image.width(),
+                room_image.height(),
+                room_collider_image.width(),
+                room_collider_image.height()
+            ),
+        ));
+    }
+    let collider = room_collider_image.to_rgba8();
+    if collider.pixels().all(|pixel| pixel.0[3] < 128) {
+        diagnostics.push(Diagnostic::warning(
+            None,
+            "the room collider image has no solid pixels, so the room has no colliders".to_string(),
+        ));
+    }
+    diagnostics
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::level_text::parse_level_text;
+
+    /// The pixels of the errors found in a text level.
+    fn error_pixels(text: &str) -> Vec<Option<IVec2>> {
+        lint_level(&parse_level_text(text).unwrap())
+            .into_iter()
+            .filter(|diagnostic| diagnostic.severity == Severity::Error)
+            .map(|diagnostic| diagnostic.pixel)
+            .collect()
+    }
+
+    #[test]
+    fn clean_level() {
+        assert_eq!(error_pixels("[tiles]\n#####\n#B.@#\n##D##\n"), vec![]);
+    }
+
+    #[test]
+    fn missing_and_extra_players() {
+        assert_eq!(error_pixels("[tiles]\n#####\n#B..#\n##D##\n"), vec![None]);
+        assert_eq!(
+            error_pixels("[tiles]\n#####\n#B@@#\n##D##\n"),
+            vec![Some(IVec2::new(3, 1))]
+        );
+    }
+
+    #[test]
+    fn unknown_color() {
+        let level =
+            parse_level_text("[legend]\nx #fa3e03\n[tiles]\n#####\n#B@x#\n##D##\n").unwrap();
+        let diagnostics = lint_level(&level);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].pixel, Some(IVec2::new(3, 1)));
+        assert!(diagnostics[0].message.contains("#fa3e03"));
+    }
+
+    #[test]
+    fn blueprint_without_a_possible_door() {
+        // The door is outside of the walls around the blueprint.
+        assert_eq!(
+            error_pixels("[tiles]\n#####\n#B#@#\n###D#\n"),
+            vec![Some(IVec2::new(1, 1))]
+        );
+    }
+
+    #[test]
+    fn bad_glue_and_requirements() {
+        assert_eq!(
+            error_pixels(
+                "[tiles]
+#####
+#Bf@#
+##D##
+[glue]
+(2, 1), (3, 1)
+[blueprints]
+(2, 1), (min_size: 2)
+"
+            ),
+            vec![Some(IVec2::new(3, 1)), Some(IVec2::new(2, 1))]
+        );
+    }
+
+    #[test]
+    fn glue_groups_can_only_be_so_big() {
+        let level = |length: usize| {
+            let cells: Vec<String> = (1..=length).map(|x| format!("({}, 1)", x)).collect();
+            format!(
+                "[tiles]\n{walls}\n#{fences}@#\n{walls}\n[glue]\n{}\n",
+                cells.join(", "),
+                walls = "#".repeat(length + 3),
+                fences = "f".repeat(length),
+            )
+        };
+        assert_eq!(error_pixels(&level(MAX_GLUE_GROUP)), vec![]);
+        assert_eq!(
+            error_pixels(&level(MAX_GLUE_GROUP + 1)),
+            vec![Some(IVec2::new(1, 1))]
+        );
+    }
+
+    #[test]
+    fn room_images_must_line_up() {
+        let room = image::DynamicImage::new_rgba8(4, 4);
+        let mut collider = image::RgbaImage::new(4, 2);
+        collider.put_pixel(0, 0, image::Rgba([0, 0, 0, 255]));
+        let diagnostics = lint_room(&room, &image::DynamicImage::ImageRgba8(collider));
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].severity, Severity::Error);
+
+        // An rgb room image, and a collider with nothing solid in it.
+        let room = image::DynamicImage::new_rgb8(4, 4);
+        let collider = image::DynamicImage::new_rgba8(4, 4);
+        let severities: Vec<Severity> = lint_room(&room, &collider)
+            .iter()
+            .map(|diagnostic| diagnostic.severity)
+            .collect();
+        assert_eq!(severities, vec![Severity::Error, Severity::Warning]);
+    }
+}
@@ -0,0 +1,259 @@
+use bevy::{asset::RenderAssetUsages, platform::collections::HashSet, prelude::*};
+
+use crate::{
+    blueprint::{
+        ActiveBlueprint, BlueprintStatus, FeedbackMode, Palette, process_blueprint_system,
+    },
+    settings::Settings,
+};
+
+pub struct OverlayPlugin;
+
+impl Plugin for OverlayPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, setup_overlay_system).add_systems(
+            Update,
+            (rebuild_overlay_system, draw_overlay_lines_system)
+                .chain()
+                .after(process_blueprint_system),
+        );
+    }
+}
+
+/// Just above the floor, so the overlay doesn't flicker against it.
+const OVERLAY_HEIGHT: f32 = 0.02;
+const OUTLINE_HEIGHT: f32 = 0.04;
+
+/// How far apart the chevrons on the leak path are, in tiles.
+const CHEVRON_SPACING: f32 = 0.8;
+/// How fast the chevrons on the leak path move, in tiles per second.
+const CHEVRON_SPEED: f32 = 1.5;
+
+/// The translucent mesh covering the active blueprint's room.
+#[derive(Component)]
+pub struct BlueprintOverlay;
+
+#[derive(Resource)]
+pub struct OverlayMaterial(pub Handle<StandardMaterial>);
+
+/// Everything the overlay mesh is built from, to tell when it needs rebuilding.
+#[derive(PartialEq, Clone, Debug)]
+pub struct OverlayKey {
+    pub interior: Vec<IVec2>,
+    pub doors: Vec<IVec2>,
+    pub is_valid: bool,
+    pub palette: Palette,
+}
+
+pub fn setup_overlay_system(
+    mut commands: Commands,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    // The colour of each cell comes from the mesh's vertex colours.
+    let material = materials.add(StandardMaterial {
+        base_color: Color::WHITE,
+        unlit: true,
+        cull_mode: None,
+        alpha_mode: AlphaMode::Blend,
+        ..default()
+    });
+    commands.insert_resource(OverlayMaterial(material));
+}
+
+/// Builds one flat quad for each cell, coloured with `color`.
+pub fn build_overlay_mesh(cells: &[(IVec2, Color)]) -> Option<Mesh> {
+    if cells.is_empty() {
+        return None;
+    }
+    let mut positions: Vec<[f32; 3]> = Vec::new();
+    let mut normals: Vec<[f32; 3]> = Vec::new();
+    let mut colors: Vec<[f32; 4]> = Vec::new();
+    let mut indices: Vec<u32> = Vec::new();
+    for &(cell, color) in cells {
+        let center = Vec3::new(cell.x as f32, OVERLAY_HEIGHT, cell.y as f32);
+        let base = positions.len() as u32;
+        for corner in [
+            Vec3::new(-0.5, 0., -0.5),
+            Vec3::new(-0.5, 0., 0.5),
+            Vec3::new(0.5, 0., 0.5),
+            Vec3::new(0.5, 0., -0.5),
+        ] {
+            positions.push((center + corner).to_array());
+            normals.push([0., 1., 0.]);
+            colors.push(color.to_linear().to_f32_array());
+        }
+        indices.extend([base, base + 1, base + 2, base, base + 2, base + 3]);
+    }
+
+    let mut mesh = Mesh::new(
+        bevy::mesh::PrimitiveTopology::TriangleList,
+        RenderAssetUsages::all(),
+    );
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, colors);
+    mesh.insert_indices(bevy::mesh::Indices::U32(indices));
+    Some(mesh)
+}
+
+fn active_status<'a>(
+    active_blueprint: &ActiveBlueprint,
+    statuses: &'a Query<&BlueprintStatus>,
+) -> Option<&'a BlueprintStatus> {
+    let active_blueprint = active_blueprint.active_blueprint.as_ref()?;
+    statuses.get(active_blueprint.blueprint_entity).ok()
+}
+
+pub fn rebuild_overlay_system(
+    mut commands: Commands,
+    settings: Res<Settings>,
+    active_blueprint: Res<ActiveBlueprint>,
+    statuses: Query<&BlueprintStatus>,
+    material: Res<OverlayMaterial>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    overlays: Query<(Entity, &Mesh3d), With<BlueprintOverlay>>,
+    mut built: Local<Option<OverlayKey>>,
+) {
+    let key = active_status(&active_blueprint, &statuses)
+        .filter(|_| settings.blueprint_feedback == FeedbackMode::Floor)
+        .map(|status| {
+            let mut doors = status.evaluation.doors.clone();
+            doors.sort_by_key(|door| (door.x, door.y));
+            OverlayKey {
+                interior: status.evaluation.interior.clone(),
+                doors,
+                is_valid: status.is_valid(),
+                palette: settings.palette,
+            }
+        });
+    if *built == key {
+        return;
+    }
+
+    for (overlay_entity, mesh) in overlays.iter() {
+        meshes.remove(&mesh.0);
+        commands.entity(overlay_entity).despawn();
+    }
+    *built = key.clone();
+
+    let Some(key) = key else {
+        return;
+    };
+    let fill = if key.is_valid {
+        key.palette.valid().with_alpha(0.35)
+    } else {
+        key.palette.invalid().with_alpha(0.2)
+    };
+    let door = Color::WHITE.with_alpha(0.6);
+    let cells: Vec<(IVec2, Color)> = key
+        .interior
+        .iter()
+        .map(|&cell| (cell, fill))
+        .chain(key.doors.iter().map(|&cell| (cell, door)))
+        .collect();
+    let Some(mesh) = build_overlay_mesh(&cells) else {
+        return;
+    };
+    commands.spawn((
+        BlueprintOverlay,
+        Mesh3d(meshes.add(mesh)),
+        MeshMaterial3d(material.0.clone()),
+        Transform::default(),
+    ));
+}
+
+/// The position and direction `distance` along a path of points, if the path is that long.
+fn point_along(points: &[Vec3], mut distance: f32) -> Option<(Vec3, Vec3)> {
+    for segment in points.windows(2) {
+        let offset = segment[1] - segment[0];
+        let length = offset.length();
+        if distance <= length && length > 0. {
+            let direction = offset / length;
+            return Some((segment[0] + direction * distance, direction));
+        }
+        distance -= length;
+    }
+    None
+}
+
+/// Draws a `>` at `at`, pointing along `direction`.
+fn draw_chevron(gizmos: &mut Gizmos, at: Vec3, direction: Vec3, size: f32, color: Color) {
+    let side = Vec3::new(-direction.z, 0., direction.x);
+    let back = at - direction * size;
+    gizmos.line(back + side * size, at, color);
+    gizmos.line(back - side * size, at, color);
+}
+
+/// Outlines the room, marks its doors, and points an arrow along the way out of it.
+pub fn draw_overlay_lines_system(
+    time: Res<Time>,
+    settings: Res<Settings>,
+    active_blueprint: Res<ActiveBlueprint>,
+    statuses: Query<&BlueprintStatus>,
+    mut gizmos: Gizmos,
+) {
+    if settings.blueprint_feedback != FeedbackMode::Floor {
+        return;
+    }
+    let Some(info) = active_blueprint.active_blueprint.as_ref() else {
+        return;
+    };
+    let Some(status) = active_status(&active_blueprint, &statuses) else {
+        return;
+    };
+    let evaluation = &status.evaluation;
+    let color = if status.is_valid() {
+        settings.palette.valid()
+    } else {
+        settings.palette.invalid()
+    };
+
+    // The outline follows every edge between the room and the cells around it.
+    let room: HashSet<IVec2> = evaluation
+        .interior
+        .iter()
+        .chain(&evaluation.doors)
+        .copied()
+        .collect();
+    for &cell in &room {
+        let center = Vec3::new(cell.x as f32, OUTLINE_HEIGHT, cell.y as f32);
+        for dir in [IVec2::X, IVec2::Y, IVec2::NEG_X, IVec2::NEG_Y] {
+            if room.contains(&(cell + dir)) {
+                continue;
+            }
+            let normal = Vec3::new(dir.x as f32, 0., dir.y as f32);
+            let side = Vec3::new(-normal.z, 0., normal.x);
+            let edge = center + normal * 0.5;
+            gizmos.line(edge + side * 0.5, edge - side * 0.5, color);
+        }
+    }
+
+    let flat = Quat::from_rotation_x(std::f32::consts::FRAC_PI_2);
+    for door in &evaluation.doors {
+        let center = Vec3::new(door.x as f32, OUTLINE_HEIGHT, door.y as f32);
+        gizmos.rect(
+            Isometry3d::new(center, flat),
+            Vec2::splat(0.6),
+            Color::WHITE,
+        );
+    }
+
+    if evaluation.leak_path.is_empty() {
+        return;
+    }
+    // The leak path runs from where the room leaked back to the blueprint, so walk it backwards.
+    let points: Vec<Vec3> = std::iter::once(info.blueprint_location)
+        .chain(evaluation.leak_path.iter().rev().copied())
+        .map(|cell| Vec3::new(cell.x as f32, OUTLINE_HEIGHT * 2., cell.y as f32))
+        .collect();
+    gizmos.linestrip(points.iter().copied(), color);
+
+    let mut distance = (time.elapsed_secs() * CHEVRON_SPEED) % CHEVRON_SPACING;
+    while let Some((at, direction)) = point_along(&points, distance) {
+        draw_chevron(&mut gizmos, at, direction, 0.15, color);
+        distance += CHEVRON_SPACING;
+    }
+    if let [.., before, end] = points[..] {
+        draw_chevron(&mut gizmos, end, (end - before).normalize(), 0.35, color);
+    }
+}
@@ -0,0 +1,290 @@
+use std::{cmp::Reverse, collections::BinaryHeap};
+
+use bevy::{
+    platform::collections::{HashMap, HashSet},
+    prelude::*,
+};
+use rand::seq::IndexedRandom;
+
+use crate::{
+    billboard::Facing,
+    blueprint::BlueprintStatus,
+    dialogue::DialogueState,
+    player::{WallGrid, gather_walls_system},
+};
+
+pub struct PathfindingPlugin;
+
+impl Plugin for PathfindingPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, move_npcs_system.after(gather_walls_system));
+    }
+}
+
+/// The most cells a search will visit before giving up, so unreachable goals stay cheap.
+pub const MAX_SEARCH_CELLS: usize = 2000;
+
+/// Finds the shortest path from `start` to `goal` through cells which aren't `blocked`, moving
+/// orthogonally. The path doesn't include `start`, and ends with `goal`.
+pub fn find_path(start: IVec2, goal: IVec2, blocked: impl Fn(IVec2) -> bool) -> Option<Vec<IVec2>> {
+    if start == goal {
+        return Some(Vec::new());
+    }
+    if blocked(goal) {
+        return None;
+    }
+
+    let heuristic = |cell: IVec2| (goal - cell).abs().element_sum();
+    let mut open: BinaryHeap<Reverse<(i32, i32, i32)>> = BinaryHeap::new();
+    let mut came_from: HashMap<IVec2, IVec2> = HashMap::new();
+    let mut cost: HashMap<IVec2, i32> = HashMap::new();
+    let mut closed: HashSet<IVec2> = HashSet::new();
+
+    cost.insert(start, 0);
+    open.push(Reverse((heuristic(start), start.x, start.y)));
+
+    while let Some(Reverse((_, x, y))) = open.pop() {
+        let current = IVec2::new(x, y);
+        if current == goal {
+            let mut path = vec![goal];
+            let mut at = goal;
+            while let Some(&previous) = came_from.get(&at) {
+                if previous == start {
+                    break;
+                }
+                path.push(previous);
+                at = previous;
+            }
+            path.reverse();
+            return Some(path);
+        }
+        if !closed.insert(current) {
+            continue;
+        }
+        if closed.len() > MAX_SEARCH_CELLS {
+            return None;
+        }
+
+        let current_cost = cost[&current];
+        for dir in [IVec2::X, IVec2::Y, IVec2::NEG_X, IVec2::NEG_Y] {
+            let neighbor = current + dir;
+            if closed.contains(&neighbor) || blocked(neighbor) {
+                continue;
+            }
+            let neighbor_cost = current_cost + 1;
+            if cost
+                .get(&neighbor)
+                .is_some_and(|&known| known <= neighbor_cost)
+            {
+                continue;
+            }
+            cost.insert(neighbor, neighbor_cost);
+            came_from.insert(neighbor, current);
+            open.push(Reverse((
+                neighbor_cost + heuristic(neighbor),
+                neighbor.x,
+                neighbor.y,
+            )));
+        }
+    }
+    None
+}
+
+/// What an NPC does when it has nowhere left to go.
+#[derive(Clone, Debug)]
+pub enum MoveBehaviour {
+    /// Stays where it is.
+    Idle,
+    /// Walks between `points` in a loop.
+    Patrol { points: Vec<IVec2>, next: usize },
+    /// Walks to random cells inside the room around a blueprint.
+    WanderRoom { blueprint: Entity },
+    /// Walks to the nearest door of the room around a blueprint, then wanders inside it.
+    GoToDoor { blueprint: Entity },
+}
+
+/// Walks an NPC along A* paths around the same walls and water that block the player.
+#[derive(Component, Clone, Debug)]
+pub struct NpcMover {
+    pub behaviour: MoveBehaviour,
+    /// The cells left to walk through, in order.
+    pub path: Vec<IVec2>,
+    /// In tiles per second.
+    pub speed: f32,
+    /// How long to stand still before picking the next destination.
+    pub wait: f32,
+}
+
+impl NpcMover {
+    pub fn new(behaviour: MoveBehaviour) -> NpcMover {
+        NpcMover {
+            behaviour,
+            path: Vec::new(),
+            speed: 1.5,
+            wait: 0.,
+        }
+    }
+
+    pub fn patrol(points: Vec<IVec2>) -> NpcMover {
+        NpcMover::new(MoveBehaviour::Patrol { points, next: 0 })
+    }
+
+    /// Whether the NPC has reached the inside of the room it was sent to.
+    pub fn is_inside_room(&self) -> bool {
+        matches!(self.behaviour, MoveBehaviour::WanderRoom { .. })
+    }
+}
+
+pub fn move_npcs_system(
+    time: Res<Time>,
+    wall_grid: Res<WallGrid>,
+    dialogue_state: Res<DialogueState>,
+    blueprints: Query<&BlueprintStatus>,
+    mut npcs: Query<(Entity, &mut Transform, &mut NpcMover, Option<&mut Facing>)>,
+) {
+    let dt = time.delta_secs();
+    let blocked = |cell: IVec2| wall_grid.walls.contains(&cell) || wall_grid.water.contains(&cell);
+    let mut rng = rand::rng();
+
+    for (npc_entity, mut npc_transform, mut mover, facing) in npcs.iter_mut() {
+        let is_talking = dialogue_state
+            .open
+            .as_ref()
+            .is_some_and(|open| open.npc == npc_entity);
+        if is_talking {
+            continue;
+        }
+
+        let position = npc_transform.translation.xz();
+        let cell = position.round().as_ivec2();
+
+        if let Some(&next_cell) = mover.path.first() {
+            if blocked(next_cell) {
+                // Something was put in the way, so find a new path.
+                mover.path.clear();
+                continue;
+            }
+            let target = next_cell.as_vec2();
+            let step = mover.speed * dt;
+            let offset = target - position;
+            let moved = if offset.length() <= step {
+                mover.path.remove(0);
+                target
+            } else {
+                position + offset.normalize() * step
+            };
+            npc_transform.translation.x = moved.x;
+            npc_transform.translation.z = moved.y;
+            if let Some(mut facing) = facing {
+                facing.heading = offset.normalize_or_zero();
+            }
+            continue;
+        }
+
+        if mover.wait > 0. {
+            mover.wait -= dt;
+            continue;
+        }
+
+        // Decide where to go next.
+        let NpcMover {
+            behaviour, path, ..
+        } = &mut *mover;
+        let destination = match behaviour {
+            MoveBehaviour::Idle => None,
+            MoveBehaviour::Patrol { points, next } => {
+                if points.is_empty() {
+                    None
+                } else {
+                    if points[*next % points.len()] == cell {
+                        *next = (*next + 1) % points.len();
+                    }
+                    Some(points[*next % points.len()])
+                }
+            }
+            MoveBehaviour::WanderRoom { blueprint } => blueprints
+                .get(*blueprint)
+                .ok()
+                .and_then(|status| status.evaluation.interior.choose(&mut rng).copied()),
+            MoveBehaviour::GoToDoor { blueprint } => {
+                let blueprint = *blueprint;
+                let doors = blueprints
+                    .get(blueprint)
+                    .map(|status| status.evaluation.doors.clone())
+                    .unwrap_or_default();
+                if doors.contains(&cell) {
+                    *behaviour = MoveBehaviour::WanderRoom { blueprint };
+                    None
+                } else {
+                    doors
+                        .into_iter()
+                        .min_by_key(|door| (*door - cell).abs().element_sum())
+                }
+            }
+        };
+
+        let Some(destination) = destination else {
+            continue;
+        };
+        match find_path(cell, destination, blocked) {
+            Some(found) => *path = found,
+            None => {
+                // Try again later, in case the way is cleared.
+                mover.wait = 1.;
+                continue;
+            }
+        }
+        mover.wait = if mover.is_inside_room() {
+            rand::random_range(1.0..3.0)
+        } else {
+            0.5
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn straight_path() {
+        let path = find_path(IVec2::ZERO, IVec2::new(3, 0), |_| false).unwrap();
+        assert_eq!(
+            path,
+            vec![IVec2::new(1, 0), IVec2::new(2, 0), IVec2::new(3, 0)]
+        );
+    }
+
+    #[test]
+    fn detours_around_a_wall() {
+        // A wall from (1, -1) to (1, 1) between the start and the goal.
+        let wall = |cell: IVec2| cell.x == 1 && cell.y.abs() <= 1;
+        let path = find_path(IVec2::ZERO, IVec2::new(2, 0), wall).unwrap();
+        // Two steps out to get past the wall, two across, and two back.
+        assert_eq!(path.len(), 6);
+        assert_eq!(path.last(), Some(&IVec2::new(2, 0)));
+        assert!(path.iter().all(|&cell| !wall(cell)));
+        for step in path.windows(2) {
+            assert_eq!((step[1] - step[0]).abs().element_sum(), 1);
+        }
+    }
+
+    #[test]
+    fn unreachable_goal() {
+        // The goal is boxed in, so the search gives up once it has visited too many cells.
+        let goal = IVec2::new(5, 0);
+        let boxed_in = |cell: IVec2| cell != goal && (cell - goal).abs().max_element() == 1;
+        assert_eq!(find_path(IVec2::ZERO, goal, boxed_in), None);
+
+        // A goal which is blocked itself fails straight away.
+        assert_eq!(find_path(IVec2::ZERO, goal, |cell| cell == goal), None);
+    }
+
+    #[test]
+    fn start_is_goal() {
+        assert_eq!(
+            find_path(IVec2::ONE, IVec2::ONE, |_| true),
+            Some(Vec::new())
+        );
+    }
+}
@@ -1,25 +1,77 @@
-use bevy::{platform::collections::HashSet, prelude::*};
+use bevy::{platform::collections::HashSet, prelude::*, window::PrimaryWindow};
+use serde::{Deserialize, Serialize};
 
-use crate::billboard::BillboardCamera;
+use crate::{
+    billboard::{
+        AnimatedBillboard, BillboardCamera, Facing, SpriteClip, animate_billboards_system,
+    },
+    camera::CameraRig,
+    controls::{ActionMap, ActionState},
+    ground::GroundGrid,
+    item::Item,
+};
 pub struct PlayerPlugin;
 
 impl Plugin for PlayerPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Startup, setup_walls_system).add_systems(
-            FixedUpdate,
-            (gather_walls_system, move_player_system, move_camera_system).chain(),
-        );
+        app.init_resource::<WaterMode>()
+            .add_message::<Splash>()
+            .add_systems(Startup, setup_walls_system)
+            .add_systems(
+                FixedUpdate,
+                (
+                    gather_walls_system,
+                    move_player_system,
+                    water_system,
+                    mouse_cursor_system,
+                )
+                    .chain(),
+            )
+            .add_systems(
+                Update,
+                (
+                    animate_player_system.before(animate_billboards_system),
+                    spawn_splash_rings_system,
+                    draw_splash_rings_system,
+                ),
+            );
     }
 }
 
 #[derive(Component, Debug)]
 pub struct Player {
     pub velocity: Vec3,
-    pub facing_direction: f32,
     /// A moving average of velocity intent.
     pub recent_velocity: Vec3,
     /// The location where they want to pick up or drop items.
     pub cursor: Vec3,
+    /// The last dry cell the player stood on, where they return to in [`WaterMode::Respawn`].
+    pub last_safe_cell: IVec2,
+    pub in_water: bool,
+}
+
+/// The duck's sprite sheet. duck_realtor.png only has a single frame so far, so every clip shows it
+/// as a placeholder until the real frames are drawn.
+pub fn player_animation() -> AnimatedBillboard {
+    let clip = |seconds_per_frame: f32| SpriteClip {
+        frames: vec![0],
+        seconds_per_frame,
+    };
+    AnimatedBillboard {
+        columns: 1,
+        rows: 1,
+        clips: [
+            ("idle", clip(0.5)),
+            ("walk", clip(0.1)),
+            ("carry", clip(0.12)),
+            ("swim", clip(0.2)),
+        ]
+        .into_iter()
+        .map(|(name, clip)| (name.to_string(), clip))
+        .collect(),
+        clip: "idle".to_string(),
+        elapsed: 0.,
+    }
 }
 
 #[derive(Component)]
@@ -27,29 +79,65 @@ pub struct Wall {
     pub enabled: bool,
 }
 
-#[derive(Component)]
-#[require(Wall { enabled: true})]
-pub struct Water {}
-
 #[derive(Component)]
 pub struct Bridge {}
 
 #[derive(Resource, Default)]
 pub struct WallGrid {
     pub walls: HashSet<IVec2>,
+    /// Water can be entered by the player (see [`WaterMode`]), but nothing can be built over it.
+    pub water: HashSet<IVec2>,
+}
+
+impl WallGrid {
+    /// Whether an item can be placed in this cell.
+    pub fn is_blocked(&self, cell: IVec2) -> bool {
+        self.walls.contains(&cell) || self.water.contains(&cell)
+    }
 }
 
 pub fn setup_walls_system(mut commands: Commands) {
-    commands.insert_resource(WallGrid {
-        walls: HashSet::new(),
-    });
+    commands.insert_resource(WallGrid::default());
+}
+
+/// What happens when the player walks into water. Chosen in the settings.
+#[derive(Resource, Copy, Clone, Eq, PartialEq, Debug, Default, Serialize, Deserialize)]
+pub enum WaterMode {
+    /// The player swims slowly through the water.
+    #[default]
+    Swim,
+    /// The player is returned to the last dry cell they stood on.
+    Respawn,
+}
+
+impl WaterMode {
+    pub const ALL: [WaterMode; 2] = [WaterMode::Swim, WaterMode::Respawn];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            WaterMode::Swim => "Swim",
+            WaterMode::Respawn => "Return to shore",
+        }
+    }
+}
+
+/// How much slower the player moves while swimming.
+pub const SWIM_SPEED_FACTOR: f32 = 0.45;
+
+/// Sent when the player falls into water.
+#[derive(Message, Copy, Clone, Debug)]
+pub struct Splash {
+    pub position: Vec3,
 }
 
 pub fn gather_walls_system(
     wall_entities: Query<(&Transform, &Wall)>,
+    ground: Res<GroundGrid>,
     mut wall_grid: ResMut<WallGrid>,
 ) {
     wall_grid.walls.clear();
+    wall_grid.water.clear();
+    wall_grid.water.extend(ground.water_cells());
     for (wall_transform, wall) in wall_entities.iter() {
         if !wall.enabled {
             continue;
@@ -64,36 +152,27 @@ pub fn move_player_system(
     mut players: Query<(
         &Transform,
         &mut Player,
+        &mut Facing,
         &mut avian3d::prelude::LinearVelocity,
     )>,
-    camera: Query<&Transform, (With<BillboardCamera>, Without<Player>)>,
-    key: Res<ButtonInput<KeyCode>>,
+    camera_rig: Res<CameraRig>,
+    action_state: Res<ActionState>,
     wall_grid: Res<WallGrid>,
+    water_mode: Res<WaterMode>,
 ) {
-    let Ok(camera) = camera.single() else {
-        return;
-    };
-
-    let forward = (camera.forward().normalize() * Vec3::new(1., 0., 1.)).normalize_or_zero();
-    let right = -Vec3::Y.cross(forward);
+    let forward = camera_rig.forward();
+    let right = camera_rig.right();
     let dt = time.delta_secs();
-    for (player_transform, mut player, mut player_velocity) in players.iter_mut() {
-        let mut target_velocity = Vec3::ZERO;
-        if key.pressed(KeyCode::KeyD) {
-            player.facing_direction = 1.;
-            target_velocity += right;
-        }
-        if key.pressed(KeyCode::KeyA) {
-            player.facing_direction = -1.;
-            target_velocity -= right;
-        }
-        if key.pressed(KeyCode::KeyW) {
-            target_velocity += forward;
-        }
-        if key.pressed(KeyCode::KeyS) {
-            target_velocity -= forward;
+    for (player_transform, mut player, mut facing, mut player_velocity) in players.iter_mut() {
+        let mut target_velocity =
+            right * action_state.move_axis.x + forward * action_state.move_axis.y;
+        if target_velocity != Vec3::ZERO {
+            facing.heading = target_velocity.xz();
         }
         target_velocity *= 3.5;
+        if *water_mode == WaterMode::Swim && player.in_water {
+            target_velocity *= SWIM_SPEED_FACTOR;
+        }
 
         player.recent_velocity = player
             .recent_velocity
@@ -107,36 +186,388 @@ pub fn move_player_system(
             player.recent_velocity = player.recent_velocity.normalize_or_zero() * 0.25;
         }
 
+        player.velocity = player.velocity.lerp(target_velocity, (dt * 12.).min(1.));
+
         // Find nearby walls and push the player out of them.
-        let mut delta_push = Vec2::ZERO;
         let player_radius = 0.35;
-        let block_half_size = 0.5;
-
-        player.velocity = player.velocity.lerp(target_velocity, (dt * 12.).min(1.));
+        let (delta_push, slide_velocity) = push_out_of_walls(
+            &wall_grid.walls,
+            player_transform.translation.xz(),
+            player.velocity.xz(),
+            dt,
+            player_radius,
+        );
+        player.velocity.x = slide_velocity.x;
+        player.velocity.z = slide_velocity.y;
 
         let current_velocity = player.velocity;
         player.cursor += dt * current_velocity * 6.; // Update faster, so it leads the player.
-        if player.cursor.distance(player_transform.translation) > 1. {
-            player.cursor = player_transform.translation.move_towards(player.cursor, 1.);
+        if player.cursor.distance(player_transform.translation) > PLAYER_REACH {
+            player.cursor = player_transform
+                .translation
+                .move_towards(player.cursor, PLAYER_REACH);
+        }
+
+        // The push is applied through the physics velocity, so that it happens over this step.
+        let push_velocity = if dt > 0. { delta_push / dt } else { Vec2::ZERO };
+
+        player_velocity.0.x = player.velocity.x + push_velocity.x;
+        player_velocity.0.z = player.velocity.z + push_velocity.y;
+    }
+}
+
+/// Resolves a circle of `radius` moving at `velocity` against the unit wall squares in `walls`.
+///
+/// Returns how far the circle must be pushed so that it ends this step outside of every wall, and
+/// its velocity with any motion into the walls removed, so that it slides along their edges. The
+/// push is measured after sliding, so together they move the circle exactly out of the walls.
+pub fn push_out_of_walls(
+    walls: &HashSet<IVec2>,
+    position: Vec2,
+    velocity: Vec2,
+    dt: f32,
+    radius: f32,
+) -> (Vec2, Vec2) {
+    let mut velocity = velocity;
+    let (_, normals) = wall_contacts(walls, position + velocity * dt, radius);
+    for normal in normals {
+        let into_wall = velocity.dot(normal);
+        if into_wall < 0. {
+            velocity -= normal * into_wall;
+        }
+    }
+
+    let (delta_push, _) = wall_contacts(walls, position + velocity * dt, radius);
+    (delta_push, velocity)
+}
+
+/// Finds how far a circle of `radius` at `at` must be pushed to leave every wall it overlaps, and
+/// the normals of the walls it overlaps.
+fn wall_contacts(walls: &HashSet<IVec2>, at: Vec2, radius: f32) -> (Vec2, Vec<Vec2>) {
+    let block_half_size = 0.5;
+    let mut delta_push = Vec2::ZERO;
+    let mut normals = Vec::new();
+
+    let center_square = at.round().as_ivec2();
+    for dx in -1..=1 {
+        for dy in -1..=1 {
+            let wall_square = center_square + IVec2::new(dx, dy);
+            if !walls.contains(&wall_square) {
+                continue;
+            }
+            // Walls are resolved one at a time, so that two walls side by side don't push twice.
+            let at = at + delta_push;
+            let wall_center = wall_square.as_vec2();
+            let closest = at.clamp(wall_center - block_half_size, wall_center + block_half_size);
+            let offset = at - closest;
+            let distance = offset.length();
+            if distance >= radius {
+                continue;
+            }
+
+            let (normal, depth) = if distance > 0.0001 {
+                (offset / distance, radius - distance)
+            } else {
+                // The center is inside the wall, so leave through the nearest side.
+                let inside = at - wall_center;
+                if inside.x.abs() > inside.y.abs() {
+                    (
+                        Vec2::new(inside.x.signum(), 0.),
+                        block_half_size - inside.x.abs() + radius,
+                    )
+                } else {
+                    (
+                        Vec2::new(0., inside.y.signum()),
+                        block_half_size - inside.y.abs() + radius,
+                    )
+                }
+            };
+
+            delta_push += normal * depth;
+            normals.push(normal);
+        }
+    }
+
+    (delta_push, normals)
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum WaterStep {
+    /// The player is on dry ground.
+    Dry,
+    /// The player is in the water. `splash` is set when they have just fallen in.
+    Swim { splash: bool },
+    /// The player fell in, and must be moved back to this cell.
+    Respawn(IVec2),
+}
+
+/// Decides what happens to the player now that they are standing in `cell`.
+pub fn water_step(
+    mode: WaterMode,
+    ground: &GroundGrid,
+    player: &mut Player,
+    cell: IVec2,
+) -> WaterStep {
+    if !ground.is_water(cell) {
+        player.last_safe_cell = cell;
+        player.in_water = false;
+        return WaterStep::Dry;
+    }
+    match mode {
+        WaterMode::Swim => {
+            let splash = !player.in_water;
+            player.in_water = true;
+            WaterStep::Swim { splash }
+        }
+        WaterMode::Respawn => {
+            player.in_water = false;
+            WaterStep::Respawn(player.last_safe_cell)
+        }
+    }
+}
+
+pub fn water_system(
+    water_mode: Res<WaterMode>,
+    ground: Res<GroundGrid>,
+    mut players: Query<(
+        &mut Transform,
+        &mut Player,
+        &mut avian3d::prelude::LinearVelocity,
+    )>,
+    mut splashes: MessageWriter<Splash>,
+) {
+    for (mut player_transform, mut player, mut player_velocity) in players.iter_mut() {
+        let cell = player_transform.translation.xz().round().as_ivec2();
+        match water_step(*water_mode, &ground, &mut player, cell) {
+            WaterStep::Dry | WaterStep::Swim { splash: false } => {}
+            WaterStep::Swim { splash: true } => {
+                splashes.write(Splash {
+                    position: player_transform.translation.with_y(0.),
+                });
+            }
+            WaterStep::Respawn(safe_cell) => {
+                splashes.write(Splash {
+                    position: player_transform.translation.with_y(0.),
+                });
+                player_transform.translation.x = safe_cell.x as f32;
+                player_transform.translation.z = safe_cell.y as f32;
+                player.velocity = Vec3::ZERO;
+                player_velocity.0 = Vec3::ZERO;
+                player.cursor = player_transform.translation;
+            }
         }
+    }
+}
+
+/// An expanding ring drawn where the player splashed into water.
+#[derive(Component)]
+pub struct SplashRing {
+    pub age: f32,
+}
+
+const SPLASH_RING_SECONDS: f32 = 0.6;
 
-        // player_transform.translation.x += delta_push.x;
-        // player_transform.translation.z += delta_push.y;
+pub fn spawn_splash_rings_system(mut commands: Commands, mut splashes: MessageReader<Splash>) {
+    for splash in splashes.read() {
+        commands.spawn((
+            SplashRing { age: 0. },
+            Transform::from_translation(splash.position),
+        ));
+    }
+}
 
-        player_velocity.0.x = player.velocity.x;
-        player_velocity.0.z = player.velocity.z;
+pub fn draw_splash_rings_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut rings: Query<(Entity, &Transform, &mut SplashRing)>,
+    mut gizmos: Gizmos,
+) {
+    for (ring_entity, ring_transform, mut ring) in rings.iter_mut() {
+        ring.age += time.delta_secs();
+        let t = ring.age / SPLASH_RING_SECONDS;
+        if t >= 1. {
+            commands.entity(ring_entity).despawn();
+            continue;
+        }
+        gizmos.circle(
+            Isometry3d::new(
+                ring_transform.translation,
+                Quat::from_rotation_x(std::f32::consts::FRAC_PI_2),
+            ),
+            0.2 + t * 0.8,
+            Color::linear_rgba(0.8, 0.9, 1., 1. - t),
+        );
     }
 }
 
-pub fn move_camera_system(
-    mut camera: Query<&mut Transform, With<BillboardCamera>>,
-    player: Query<&Transform, (With<Player>, Without<BillboardCamera>)>,
+/// How far from the player the cursor can be.
+pub const PLAYER_REACH: f32 = 1.;
+
+/// With mouse targeting enabled, moves the cursor to where the mouse pointer meets the ground,
+/// as far towards it as the player can reach.
+pub fn mouse_cursor_system(
+    action_map: Res<ActionMap>,
+    window: Query<&Window, With<PrimaryWindow>>,
+    camera: Query<(&Camera, &GlobalTransform), With<BillboardCamera>>,
+    mut players: Query<(&Transform, &mut Player)>,
 ) {
-    let Ok(player) = player.single() else {
+    if !action_map.mouse_targeting {
+        return;
+    }
+    let Ok(window) = window.single() else {
+        return;
+    };
+    let Some(mouse_position) = window.cursor_position() else {
+        return;
+    };
+    let Ok((camera, camera_transform)) = camera.single() else {
+        return;
+    };
+    let Ok(ray) = camera.viewport_to_world(camera_transform, mouse_position) else {
         return;
     };
-    for mut camera in camera.iter_mut() {
-        *camera = Transform::from_translation(player.translation + Vec3::new(0., 8.5, 9.5))
-            .looking_at(player.translation, Vec3::Y);
+    let Some(distance) = ray.intersect_plane(Vec3::ZERO, InfinitePlane3d::new(Vec3::Y)) else {
+        return;
+    };
+    let ground_point = ray.get_point(distance);
+
+    for (player_transform, mut player) in players.iter_mut() {
+        let target = Vec3::new(
+            ground_point.x,
+            player_transform.translation.y,
+            ground_point.z,
+        );
+        player.cursor = player_transform
+            .translation
+            .move_towards(target, PLAYER_REACH);
+    }
+}
+
+/// The name of the clip the player should play, from how they are moving and what they are holding.
+pub fn player_clip(player: &Player, is_carrying: bool) -> &'static str {
+    if player.in_water {
+        "swim"
+    } else if is_carrying {
+        "carry"
+    } else if player.velocity.length() > 0.5 {
+        "walk"
+    } else {
+        "idle"
+    }
+}
+
+/// Picks the player's animation clip from how they are moving and what they are holding.
+pub fn animate_player_system(
+    items: Query<&Item>,
+    mut players: Query<(&Player, &mut AnimatedBillboard)>,
+) {
+    let is_carrying = items.iter().any(|item| item.is_held.is_some());
+    for (player, mut animation) in players.iter_mut() {
+        animation.play(player_clip(player, is_carrying));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ground::GroundTile;
+
+    fn test_player() -> Player {
+        Player {
+            velocity: Vec3::ZERO,
+            recent_velocity: Vec3::ZERO,
+            cursor: Vec3::ZERO,
+            last_safe_cell: IVec2::ZERO,
+            in_water: false,
+        }
+    }
+
+    /// A floor tile at x = 0 and 1, and water at x = 2 and 3.
+    fn test_ground() -> GroundGrid {
+        let mut ground = GroundGrid::default();
+        ground.set(IVec2::new(0, 0), GroundTile::Floor);
+        ground.set(IVec2::new(1, 0), GroundTile::Floor);
+        ground.set(IVec2::new(2, 0), GroundTile::Water);
+        ground.set(IVec2::new(3, 0), GroundTile::Water);
+        ground
+    }
+
+    #[test]
+    fn swim_mode_splashes_once_then_swims() {
+        let ground = test_ground();
+        let mut player = test_player();
+
+        let steps = [1, 2, 3, 1].map(|x| {
+            let step = water_step(WaterMode::Swim, &ground, &mut player, IVec2::new(x, 0));
+            (step, player_clip(&player, false))
+        });
+        assert_eq!(
+            steps,
+            [
+                (WaterStep::Dry, "idle"),
+                (WaterStep::Swim { splash: true }, "swim"),
+                (WaterStep::Swim { splash: false }, "swim"),
+                (WaterStep::Dry, "idle"),
+            ]
+        );
+    }
+
+    #[test]
+    fn respawn_mode_returns_to_last_safe_cell() {
+        let ground = test_ground();
+        let mut player = test_player();
+
+        assert_eq!(
+            water_step(WaterMode::Respawn, &ground, &mut player, IVec2::new(1, 0)),
+            WaterStep::Dry
+        );
+        assert_eq!(
+            water_step(WaterMode::Respawn, &ground, &mut player, IVec2::new(2, 0)),
+            WaterStep::Respawn(IVec2::new(1, 0))
+        );
+        assert!(!player.in_water);
+        assert_eq!(player.last_safe_cell, IVec2::new(1, 0));
+    }
+
+    /// Steps a player the way `move_player_system` does, returning where they end up.
+    fn walk(walls: &HashSet<IVec2>, mut position: Vec2, velocity: Vec2, steps: usize) -> Vec2 {
+        let dt = 1. / 64.;
+        for _ in 0..steps {
+            let (delta_push, slide_velocity) =
+                push_out_of_walls(walls, position, velocity, dt, 0.35);
+            position += slide_velocity * dt + delta_push;
+        }
+        position
+    }
+
+    #[test]
+    fn clip_follows_what_the_player_is_doing() {
+        let mut player = test_player();
+        assert_eq!(player_clip(&player, false), "idle");
+        assert_eq!(player_clip(&player, true), "carry");
+
+        player.velocity = Vec3::new(1., 0., 0.);
+        assert_eq!(player_clip(&player, false), "walk");
+        assert_eq!(player_clip(&player, true), "carry");
+
+        // Swimming wins over everything else.
+        player.in_water = true;
+        assert_eq!(player_clip(&player, true), "swim");
+        assert!(player_animation().clips.contains_key("swim"));
+    }
+
+    #[test]
+    fn walking_into_a_wall_stays_against_it() {
+        // A wall whose left edge is at x = 0.5, and a player touching it.
+        let walls = HashSet::from_iter([IVec2::new(1, 0)]);
+        let touching = Vec2::new(0.5 - 0.35, 0.);
+
+        let position = walk(&walls, touching, Vec2::X, 64);
+        assert!(position.distance(touching) < 0.0001, "{position}");
+
+        // Walking diagonally slides along the wall without leaving it.
+        let position = walk(&walls, touching, Vec2::new(1., 1.), 16);
+        assert!((position.x - touching.x).abs() < 0.0001, "{position}");
+        assert!(position.y > 0.2, "{position}");
     }
 }
@@ -18,6 +18,27 @@ pub struct RoomSpec {
     pub collider: avian3d::prelude::Collider,
 }
 
+pub type ImageLayer = image::ImageBuffer<image::Rgba<u8>, Vec<u8>>;
+
+/// A box-shaped piece of a room's collider, in room-local coordinates.
+pub struct RoomColliderBox {
+    pub center: Vec3,
+    pub size: Vec3,
+}
+
+/// The mesh and collider boxes generated from a room image pair, before they are turned into assets.
+pub struct RoomGeometry {
+    pub mesh: Mesh,
+    pub colliders: Vec<RoomColliderBox>,
+}
+
+pub fn open_room_layer(path: &str) -> Result<ImageLayer, Box<dyn std::error::Error>> {
+    match image::open(path)? {
+        image::DynamicImage::ImageRgba8(layer) => Ok(layer),
+        layer => Err(format!("the image is {:?}, but it must be rgba8", layer.color()).into()),
+    }
+}
+
 fn load_room(
     room_image_path: &str,
     room_collider_image: &str,
@@ -26,15 +47,60 @@ fn load_room(
     materials: &mut Assets<StandardMaterial>,
 ) -> RoomSpec {
     let room_image =
-        image::open(&format!("assets/{}", room_image_path)).expect("can load room image");
-    let room_image = room_image.as_rgba8().expect("is rgba8");
-
-    let room_collider_image = image::open(&format!("assets/{}", room_collider_image))
+        open_room_layer(&format!("assets/{}", room_image_path)).expect("can load room image");
+    let room_collider_image = open_room_layer(&format!("assets/{}", room_collider_image))
         .expect("can load room collider image");
-    let room_collider_image = room_collider_image.as_rgba8().expect("is rgba8");
 
-    type ImageLayer = image::ImageBuffer<image::Rgba<u8>, Vec<u8>>;
+    let geometry = build_room_geometry(&room_image, &room_collider_image);
+
+    let room_collider = avian3d::prelude::Collider::compound(
+        geometry
+            .colliders
+            .iter()
+            .map(|collider_box| {
+                (
+                    collider_box.center,
+                    avian3d::prelude::Rotation::IDENTITY,
+                    avian3d::prelude::Collider::cuboid(
+                        collider_box.size.x,
+                        collider_box.size.y,
+                        collider_box.size.z,
+                    ),
+                )
+            })
+            .collect(),
+    );
+
+    let mesh_handle = meshes.add(geometry.mesh);
 
+    let material_handle = materials.add(StandardMaterial {
+        base_color_texture: Some(asset_server.load_with_settings(
+            room_image_path.to_string(),
+            |settings: &mut bevy::image::ImageLoaderSettings| {
+                settings.sampler =
+                    bevy::image::ImageSampler::Descriptor(bevy::image::ImageSamplerDescriptor {
+                        mag_filter: bevy::image::ImageFilterMode::Nearest,
+                        ..default()
+                    });
+            },
+        )),
+        base_color: Color::linear_rgb(0.7, 0.8, 0.9),
+        ..default()
+    });
+
+    RoomSpec {
+        material: material_handle,
+        mesh: mesh_handle,
+        collider: room_collider,
+    }
+}
+
+/// Builds the room mesh (with flat normals) and its collider boxes from the room image and the
+/// room collider image.
+pub fn build_room_geometry(
+    room_image: &ImageLayer,
+    room_collider_image: &ImageLayer,
+) -> RoomGeometry {
     let is_solid = |layer: &ImageLayer, p: IVec2| -> bool {
         if p.x < 0 || p.y < 0 || p.x >= layer.width() as i32 || p.y >= layer.height() as i32 {
             return false;
@@ -63,7 +129,7 @@ fn load_room(
 
     for x in 0..room_image.width() {
         for y in 0..room_image.height() {
-            let h = if is_solid(&room_image, IVec2::new(x as i32, y as i32)) {
+            let h = if is_solid(room_image, IVec2::new(x as i32, y as i32)) {
                 2
             } else {
                 1
@@ -139,7 +205,7 @@ fn load_room(
             IVec2::new(-1, -1),
         ] {
             let neighbor_pixel = v.xz() + shift;
-            if is_solid(&room_image, neighbor_pixel) {
+            if is_solid(room_image, neighbor_pixel) {
                 sum_uv += (shift.as_vec2() + 0.5) * 0.1 / room_image.width() as f32;
             }
         }
@@ -151,15 +217,14 @@ fn load_room(
     mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, attr_uv0);
     mesh.insert_indices(bevy::mesh::Indices::U32(triangles));
 
-    let mut room_colliders: Vec<(Vec3, avian3d::prelude::Rotation, avian3d::prelude::Collider)> =
-        Vec::new();
+    let mut room_colliders: Vec<RoomColliderBox> = Vec::new();
 
     {
         let mut visited: HashSet<IVec2> = HashSet::new();
         for x in 0..room_collider_image.width() as i32 {
             for y in 0..room_collider_image.height() as i32 {
                 let p = IVec2::new(x, y);
-                if !is_solid(&room_collider_image, p) || visited.contains(&p) {
+                if !is_solid(room_collider_image, p) || visited.contains(&p) {
                     continue;
                 }
 
@@ -171,7 +236,7 @@ fn load_room(
                     region_list.push(c);
                     for dir in [IVec2::X, IVec2::Y, IVec2::NEG_X, IVec2::NEG_Y] {
                         let n = c + dir;
-                        if !is_solid(&room_collider_image, n)
+                        if !is_solid(room_collider_image, n)
                             || visited.contains(&n)
                             || room_collider_image[(n.x as u32, n.y as u32)].0[..3]
                                 != room_collider_image[(p.x as u32, p.y as u32)].0[..3]
@@ -193,49 +258,24 @@ fn load_room(
                 }
                 let lower = (to_uv(bound_min) - 0.5) * room_size;
                 let upper = (to_uv(bound_max + IVec2::new(1, 1)) - 0.5) * room_size;
-                room_colliders.push((
-                    Vec3::new(
+                room_colliders.push(RoomColliderBox {
+                    center: Vec3::new(
                         (lower.x + upper.x) / 2.,
                         room_collider_height / 2.,
                         (lower.y + upper.y) / 2.,
                     ),
-                    avian3d::prelude::Rotation::IDENTITY,
-                    avian3d::prelude::Collider::cuboid(
-                        upper.x - lower.x,
-                        room_collider_height,
-                        upper.y - lower.y,
-                    ),
-                ));
+                    size: Vec3::new(upper.x - lower.x, room_collider_height, upper.y - lower.y),
+                });
             }
         }
     }
 
-    let room_collider = avian3d::prelude::Collider::compound(room_colliders);
-
     mesh.duplicate_vertices();
     mesh.compute_flat_normals();
 
-    let mesh_handle = meshes.add(mesh);
-
-    let material_handle = materials.add(StandardMaterial {
-        base_color_texture: Some(asset_server.load_with_settings(
-            room_image_path.to_string(),
-            |settings: &mut bevy::image::ImageLoaderSettings| {
-                settings.sampler =
-                    bevy::image::ImageSampler::Descriptor(bevy::image::ImageSamplerDescriptor {
-                        mag_filter: bevy::image::ImageFilterMode::Nearest,
-                        ..default()
-                    });
-            },
-        )),
-        base_color: Color::linear_rgb(0.7, 0.8, 0.9),
-        ..default()
-    });
-
-    RoomSpec {
-        material: material_handle,
-        mesh: mesh_handle,
-        collider: room_collider,
+    RoomGeometry {
+        mesh,
+        colliders: room_colliders,
     }
 }
 
@@ -0,0 +1,382 @@
+use bevy::{
+    prelude::*,
+    window::{PresentMode, PrimaryWindow},
+};
+use bevy_framepace::{FramepaceSettings, Limiter};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    audio::AudioVolumes,
+    billboard::BillboardShadowSettings,
+    blueprint::{FeedbackMode, Palette},
+    controls::{Action, ActionMap, ActionState, update_action_state_system},
+    player::WaterMode,
+};
+
+/// Where the player's settings and control bindings are saved.
+pub const SETTINGS_PATH: &str = "settings.ron";
+
+pub struct SettingsPlugin;
+
+impl Plugin for SettingsPlugin {
+    fn build(&self, app: &mut App) {
+        let (settings, action_map) = load_settings();
+        app.insert_resource(settings)
+            .insert_resource(action_map)
+            .init_resource::<SettingsMenu>()
+            .add_systems(Startup, setup_settings_menu_system)
+            .add_systems(
+                PreUpdate,
+                settings_menu_input_system.after(update_action_state_system),
+            )
+            .add_systems(
+                Update,
+                (
+                    apply_settings_system,
+                    save_settings_system,
+                    show_settings_menu_system,
+                ),
+            );
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub enum FrameLimit {
+    /// Match the display's refresh rate.
+    Auto,
+    Fps(u32),
+    Off,
+}
+
+impl FrameLimit {
+    /// The choices offered in the settings menu, in order.
+    const CHOICES: [FrameLimit; 5] = [
+        FrameLimit::Auto,
+        FrameLimit::Fps(30),
+        FrameLimit::Fps(60),
+        FrameLimit::Fps(120),
+        FrameLimit::Off,
+    ];
+
+    fn limiter(self) -> Limiter {
+        match self {
+            FrameLimit::Auto => Limiter::Auto,
+            FrameLimit::Fps(fps) => Limiter::from_framerate(fps as f64),
+            FrameLimit::Off => Limiter::Off,
+        }
+    }
+}
+
+#[derive(Resource, Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub vsync: bool,
+    pub frame_limit: FrameLimit,
+    pub shadows: bool,
+    /// From 0 to 1.
+    pub master_volume: f32,
+    /// Scales all of the UI, including text.
+    pub text_scale: f32,
+    pub blueprint_feedback: FeedbackMode,
+    pub palette: Palette,
+    pub water_mode: WaterMode,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            vsync: true,
+            frame_limit: FrameLimit::Auto,
+            shadows: !cfg!(target_arch = "wasm32"),
+            master_volume: 1.,
+            text_scale: 1.,
+            blueprint_feedback: FeedbackMode::default(),
+            palette: Palette::default(),
+            water_mode: WaterMode::default(),
+        }
+    }
+}
+
+/// Everything saved in [`SETTINGS_PATH`].
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+struct SettingsFile {
+    settings: Settings,
+    controls: ActionMap,
+}
+
+/// Loads the saved settings and bindings, or the defaults if nothing has been saved yet. The file
+/// is only written once the player changes something.
+fn load_settings() -> (Settings, ActionMap) {
+    let Ok(text) = std::fs::read_to_string(SETTINGS_PATH) else {
+        return (Settings::default(), ActionMap::default());
+    };
+    match ron::from_str::<SettingsFile>(&text) {
+        Ok(mut file) => {
+            file.controls.fill_in_missing_bindings();
+            (file.settings, file.controls)
+        }
+        Err(err) => {
+            warn!("ignoring {}: {}", SETTINGS_PATH, err);
+            (Settings::default(), ActionMap::default())
+        }
+    }
+}
+
+pub fn save_settings_system(settings: Res<Settings>, action_map: Res<ActionMap>) {
+    // Both are "changed" when they are first inserted, but there's nothing new to save then.
+    let settings_changed = settings.is_changed() && !settings.is_added();
+    let action_map_changed = action_map.is_changed() && !action_map.is_added();
+    if !settings_changed && !action_map_changed {
+        return;
+    }
+    let file = SettingsFile {
+        settings: settings.clone(),
+        controls: action_map.clone(),
+    };
+    let text = ron::ser::to_string_pretty(&file, ron::ser::PrettyConfig::default())
+        .expect("settings can be serialized");
+    if let Err(err) = std::fs::write(SETTINGS_PATH, text) {
+        warn!("cannot save {}: {}", SETTINGS_PATH, err);
+    }
+}
+
+pub fn apply_settings_system(
+    settings: Res<Settings>,
+    mut windows: Query<&mut Window, With<PrimaryWindow>>,
+    mut framepace: ResMut<FramepaceSettings>,
+    mut lights: Query<&mut DirectionalLight>,
+    mut billboard_shadows: ResMut<BillboardShadowSettings>,
+    mut volumes: ResMut<AudioVolumes>,
+    mut ui_scale: ResMut<UiScale>,
+    mut water_mode: ResMut<WaterMode>,
+) {
+    if !settings.is_changed() {
+        return;
+    }
+    for mut window in windows.iter_mut() {
+        window.present_mode = if settings.vsync {
+            PresentMode::AutoVsync
+        } else {
+            PresentMode::AutoNoVsync
+        };
+    }
+    framepace.limiter = settings.frame_limit.limiter();
+    for mut light in lights.iter_mut() {
+        light.shadows_enabled = settings.shadows;
+    }
+    billboard_shadows.cast_shadows = settings.shadows;
+    volumes.master = settings.master_volume;
+    ui_scale.0 = settings.text_scale;
+    *water_mode = settings.water_mode;
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+enum SettingsRow {
+    Vsync,
+    FrameLimit,
+    Shadows,
+    MasterVolume,
+    TextScale,
+    BlueprintFeedback,
+    Palette,
+    WaterMode,
+}
+
+impl SettingsRow {
+    const ALL: [SettingsRow; 8] = [
+        SettingsRow::Vsync,
+        SettingsRow::FrameLimit,
+        SettingsRow::Shadows,
+        SettingsRow::MasterVolume,
+        SettingsRow::TextScale,
+        SettingsRow::BlueprintFeedback,
+        SettingsRow::Palette,
+        SettingsRow::WaterMode,
+    ];
+
+    fn describe(self, settings: &Settings) -> String {
+        let on_off = |on: bool| if on { "On" } else { "Off" };
+        match self {
+            SettingsRow::Vsync => format!("V-Sync: {}", on_off(settings.vsync)),
+            SettingsRow::FrameLimit => match settings.frame_limit {
+                FrameLimit::Auto => "Frame limit: Auto".to_string(),
+                FrameLimit::Fps(fps) => format!("Frame limit: {} FPS", fps),
+                FrameLimit::Off => "Frame limit: Off".to_string(),
+            },
+            SettingsRow::Shadows => format!("Shadows: {}", on_off(settings.shadows)),
+            SettingsRow::MasterVolume => {
+                format!("Volume: {:.0}%", settings.master_volume * 100.)
+            }
+            SettingsRow::TextScale => format!("Text size: {:.0}%", settings.text_scale * 100.),
+            SettingsRow::BlueprintFeedback => {
+                format!("Room markers: {}", settings.blueprint_feedback.name())
+            }
+            SettingsRow::Palette => format!("Colours: {}", settings.palette.name()),
+            SettingsRow::WaterMode => format!("Water: {}", settings.water_mode.name()),
+        }
+    }
+
+    /// Moves this setting one step in `direction`, which is -1 or 1.
+    fn change(self, settings: &mut Settings, direction: i32) {
+        match self {
+            SettingsRow::Vsync => settings.vsync = !settings.vsync,
+            SettingsRow::FrameLimit => {
+                settings.frame_limit = cycle(&FrameLimit::CHOICES, settings.frame_limit, direction);
+            }
+            SettingsRow::Shadows => settings.shadows = !settings.shadows,
+            SettingsRow::MasterVolume => {
+                settings.master_volume =
+                    (settings.master_volume + direction as f32 * 0.1).clamp(0., 1.);
+            }
+            SettingsRow::TextScale => {
+                settings.text_scale =
+                    (settings.text_scale + direction as f32 * 0.25).clamp(0.75, 2.);
+            }
+            SettingsRow::BlueprintFeedback => {
+                settings.blueprint_feedback =
+                    cycle(&FeedbackMode::ALL, settings.blueprint_feedback, direction);
+            }
+            SettingsRow::Palette => {
+                settings.palette = cycle(&Palette::ALL, settings.palette, direction);
+            }
+            SettingsRow::WaterMode => {
+                settings.water_mode = cycle(&WaterMode::ALL, settings.water_mode, direction);
+            }
+        }
+    }
+}
+
+/// The choice after `current`, or before it if `direction` is -1, wrapping around.
+fn cycle<T: Copy + PartialEq>(choices: &[T], current: T, direction: i32) -> T {
+    let index = choices
+        .iter()
+        .position(|&choice| choice == current)
+        .unwrap_or(0) as i32;
+    let next = (index + direction).rem_euclid(choices.len() as i32);
+    choices[next as usize]
+}
+
+/// The settings menu is shown while the game is paused.
+#[derive(Resource, Default)]
+pub struct SettingsMenu {
+    pub selected: usize,
+}
+
+#[derive(Component)]
+pub struct SettingsMenuRoot;
+
+#[derive(Component)]
+pub struct SettingsMenuText;
+
+pub fn setup_settings_menu_system(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let font = asset_server.load("fonts/delius/Delius-Regular.ttf");
+    commands
+        .spawn((
+            SettingsMenuRoot,
+            Node {
+                display: Display::None,
+                position_type: PositionType::Absolute,
+                width: percent(100),
+                height: percent(100),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+        ))
+        .with_children(|builder| {
+            builder
+                .spawn((
+                    Node {
+                        flex_direction: FlexDirection::Column,
+                        padding: UiRect::axes(px(30), px(20)),
+                        row_gap: px(10),
+                        ..default()
+                    },
+                    BackgroundColor(Color::linear_rgba(0.05, 0.05, 0.15, 0.9)),
+                ))
+                .with_children(|builder| {
+                    builder.spawn((
+                        Text::new("Paused"),
+                        TextColor(Color::linear_rgb(1., 0.85, 0.4)),
+                        TextFont {
+                            font: font.clone(),
+                            font_size: 35.0,
+                            ..default()
+                        },
+                    ));
+                    builder.spawn((
+                        SettingsMenuText,
+                        Text::default(),
+                        TextColor(Color::linear_rgb(1., 1., 1.)),
+                        TextFont {
+                            font: font.clone(),
+                            font_size: 25.0,
+                            ..default()
+                        },
+                    ));
+                });
+        });
+}
+
+pub fn settings_menu_input_system(
+    time: Res<Time<Virtual>>,
+    action_state: Res<ActionState>,
+    mut menu: ResMut<SettingsMenu>,
+    mut settings: ResMut<Settings>,
+) {
+    if !time.is_paused() {
+        return;
+    }
+    let rows = SettingsRow::ALL.len();
+    if action_state.just_pressed(Action::MoveUp) {
+        menu.selected = (menu.selected + rows - 1) % rows;
+    }
+    if action_state.just_pressed(Action::MoveDown) {
+        menu.selected = (menu.selected + 1) % rows;
+    }
+    let direction = if action_state.just_pressed(Action::MoveLeft) {
+        -1
+    } else if action_state.just_pressed(Action::MoveRight)
+        || action_state.just_pressed(Action::Interact)
+    {
+        1
+    } else {
+        0
+    };
+    if direction != 0 {
+        SettingsRow::ALL[menu.selected].change(&mut settings, direction);
+    }
+}
+
+pub fn show_settings_menu_system(
+    time: Res<Time<Virtual>>,
+    menu: Res<SettingsMenu>,
+    settings: Res<Settings>,
+    mut root: Query<&mut Node, With<SettingsMenuRoot>>,
+    mut text: Query<&mut Text, With<SettingsMenuText>>,
+) {
+    let Ok(mut root) = root.single_mut() else {
+        return;
+    };
+    if !time.is_paused() {
+        root.display = Display::None;
+        return;
+    }
+    root.display = Display::Flex;
+
+    let Ok(mut text) = text.single_mut() else {
+        return;
+    };
+    let mut lines: Vec<String> = SettingsRow::ALL
+        .iter()
+        .enumerate()
+        .map(|(index, row)| {
+            let marker = if index == menu.selected { ">" } else { " " };
+            format!("{} {}", marker, row.describe(&settings))
+        })
+        .collect();
+    lines.push(String::new());
+    lines.push("Up and down to choose, left and right to change.".to_string());
+    text.0 = lines.join("\n");
+}
@@ -0,0 +1,734 @@
+//! A headless solver which checks that a level's blueprints can all be satisfied by moving its
+//! items around.
+
+use std::collections::VecDeque;
+
+use bevy::{
+    platform::collections::{HashMap, HashSet, hash_map::Entry},
+    prelude::*,
+};
+
+use crate::{
+    blueprint::{
+        BlueprintCell, BlueprintEvaluation, BlueprintGrid, BlueprintRequirements,
+        evaluate_blueprint,
+    },
+    jobs::JobSpec,
+    level::{Level, LevelTile},
+};
+
+/// The most states [`Puzzle::solve`] will visit before giving up.
+pub const MAX_SOLVER_STATES: usize = 200_000;
+
+/// The cells around a cell, which the player can reach with their cursor.
+const NEIGHBORS: [IVec2; 8] = [
+    IVec2::new(1, 0),
+    IVec2::new(1, 1),
+    IVec2::new(0, 1),
+    IVec2::new(-1, 1),
+    IVec2::new(-1, 0),
+    IVec2::new(-1, -1),
+    IVec2::new(0, -1),
+    IVec2::new(1, -1),
+];
+
+fn cell_key(cell: IVec2) -> (i32, i32) {
+    (cell.x, cell.y)
+}
+
+/// A group of glued items, which are always moved together.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct Piece {
+    /// Each item's cell, and whether it blocks like a wall. Kept sorted, so equal pieces compare
+    /// equal.
+    pub items: Vec<(IVec2, bool)>,
+}
+
+impl Piece {
+    fn new(mut items: Vec<(IVec2, bool)>) -> Piece {
+        items.sort_by_key(|&(cell, is_wall)| (cell_key(cell), is_wall));
+        Piece { items }
+    }
+
+    /// The piece's items relative to `anchor`, after turning it `quarter_turns` times.
+    fn turned(&self, anchor: IVec2, quarter_turns: u8) -> Vec<(IVec2, bool)> {
+        self.items
+            .iter()
+            .map(|&(cell, is_wall)| {
+                let mut offset = cell - anchor;
+                for _ in 0..quarter_turns {
+                    offset = offset.perp();
+                }
+                (offset, is_wall)
+            })
+            .collect()
+    }
+
+    /// The piece with the items at `offsets` from `cursor`.
+    fn placed(offsets: &[(IVec2, bool)], cursor: IVec2) -> Piece {
+        Piece::new(
+            offsets
+                .iter()
+                .map(|&(offset, is_wall)| (cursor + offset, is_wall))
+                .collect(),
+        )
+    }
+}
+
+/// Picking up the piece at `pick_up`, turning it, and putting it down with the cursor at
+/// `place_at` while standing at `stand`.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct Move {
+    pub pick_up: IVec2,
+    pub quarter_turns: u8,
+    pub place_at: IVec2,
+    pub stand: IVec2,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct PuzzleState {
+    /// Sorted, so that states with the same pieces in the same places compare equal.
+    pub pieces: Vec<Piece>,
+    /// The first cell the player can walk to, so that states where the player can walk to the same
+    /// cells compare equal.
+    pub player: IVec2,
+}
+
+impl PuzzleState {
+    /// The pieces of this state with the piece at `index` replaced by `moved`.
+    fn with_piece(&self, index: usize, moved: Piece) -> Vec<Piece> {
+        let mut pieces = self.pieces.clone();
+        pieces[index] = moved;
+        pieces.sort_by_key(|piece| cell_key(piece.items[0].0));
+        pieces
+    }
+}
+
+/// The cells covered by every piece but the one at `index`.
+fn occupied_by_others(pieces: &[Piece], index: usize) -> HashSet<IVec2> {
+    pieces
+        .iter()
+        .enumerate()
+        .filter(|&(other, _)| other != index)
+        .flat_map(|(_, other)| other.items.iter().map(|&(cell, _)| cell))
+        .collect()
+}
+
+/// The cells around a cell in order, so that each is next to the one before it.
+const RING: [IVec2; 8] = [
+    IVec2::new(0, -1),
+    IVec2::new(1, -1),
+    IVec2::new(1, 0),
+    IVec2::new(1, 1),
+    IVec2::new(0, 1),
+    IVec2::new(-1, 1),
+    IVec2::new(-1, 0),
+    IVec2::new(-1, -1),
+];
+
+/// Whether the player can still walk between every cell of `region` that `piece` doesn't put a
+/// wall on. Only the cells around each wall are checked, so this can say no when the region is
+/// still in one piece.
+fn stays_connected(region: &HashSet<IVec2>, piece: &Piece) -> bool {
+    let mut walls: Vec<IVec2> = Vec::new();
+    for &(cell, is_wall) in &piece.items {
+        if !is_wall || !region.contains(&cell) {
+            continue;
+        }
+        walls.push(cell);
+        let open: Vec<bool> = RING
+            .iter()
+            .map(|&offset| region.contains(&(cell + offset)) && !walls.contains(&(cell + offset)))
+            .collect();
+        // The cells on either side of the wall must all be joined up by the cells around it, so
+        // count the runs of open cells around it which include a side.
+        let mut sides_joined = 0;
+        for start in 0..8 {
+            if !open[start] || open[(start + 7) % 8] {
+                continue;
+            }
+            if (start..start + 8)
+                .take_while(|&at| open[at % 8])
+                .any(|at| at % 2 == 0)
+            {
+                sides_joined += 1;
+            }
+        }
+        if sides_joined > 1 {
+            return false;
+        }
+    }
+    true
+}
+
+#[derive(Debug)]
+pub enum SolveResult {
+    /// The fewest moves which finish the level.
+    Solved(Vec<Move>),
+    /// Every reachable arrangement was tried, and none of them finish the level.
+    Unsolvable,
+    /// The search hit its limit on states before finding an answer.
+    GaveUp { states: usize },
+}
+
+/// A level's fixed layout, its blueprints, and where its pieces start.
+pub struct Puzzle {
+    pub size: IVec2,
+    /// The walls, water and doors, which never move.
+    pub fixed: BlueprintGrid,
+    /// Each blueprint and what its room needs.
+    pub blueprints: Vec<(IVec2, BlueprintRequirements)>,
+    pub start: PuzzleState,
+    /// Whether the player can walk on each cell, by [`Puzzle::index`], when there are no pieces.
+    walkable: Vec<bool>,
+}
+
+impl Puzzle {
+    /// Builds the puzzle for a level, with the blueprints its jobs ask for first, in job order.
+    pub fn new(level: &Level, jobs: &[JobSpec]) -> Result<Puzzle, String> {
+        let mut fixed: BlueprintGrid = HashMap::new();
+        let mut player = None;
+        let mut blueprints: Vec<(IVec2, BlueprintRequirements)> = jobs
+            .iter()
+            .map(|job| (IVec2::from(job.blueprint_cell), job.requirements.clone()))
+            .collect();
+        for (cell, tile) in level.cells() {
+            match tile {
+                LevelTile::Wall => {
+                    fixed.insert(cell, BlueprintCell::Wall);
+                }
+                LevelTile::Water => {
+                    fixed.insert(cell, BlueprintCell::Water);
+                }
+                LevelTile::Door => {
+                    fixed.insert(cell, BlueprintCell::Door);
+                }
+                LevelTile::Player => player = Some(cell),
+                LevelTile::Blueprint
+                    if !blueprints.iter().any(|(location, _)| *location == cell) =>
+                {
+                    blueprints.push((cell, level.requirements(cell)));
+                }
+                _ => {}
+            }
+        }
+        let Some(player) = player else {
+            return Err("the level has no player".to_string());
+        };
+
+        let mut grouped: HashSet<IVec2> = HashSet::new();
+        let mut pieces = Vec::new();
+        for item in level.items() {
+            if grouped.contains(&item.cell) {
+                continue;
+            }
+            let cells: Vec<IVec2> = std::iter::once(item.cell)
+                .chain(item.glued.iter().map(|&offset| item.cell + offset))
+                .collect();
+            grouped.extend(cells.iter().copied());
+            pieces.push(Piece::new(
+                cells
+                    .into_iter()
+                    .map(|cell| {
+                        (
+                            cell,
+                            level.get(cell).is_some_and(|tile| tile.is_wall_item()),
+                        )
+                    })
+                    .collect(),
+            ));
+        }
+        pieces.sort_by_key(|piece| cell_key(piece.items[0].0));
+
+        let size = IVec2::new(level.width as i32, level.height as i32);
+        let walkable = (0..size.y)
+            .flat_map(|y| (0..size.x).map(move |x| IVec2::new(x, y)))
+            .map(|cell| {
+                !matches!(
+                    fixed.get(&cell),
+                    Some(BlueprintCell::Wall | BlueprintCell::Water)
+                )
+            })
+            .collect();
+        let mut puzzle = Puzzle {
+            size,
+            fixed,
+            blueprints,
+            start: PuzzleState {
+                pieces: Vec::new(),
+                player,
+            },
+            walkable,
+        };
+        puzzle.start = puzzle.state(pieces, player);
+        Ok(puzzle)
+    }
+
+    fn in_bounds(&self, cell: IVec2) -> bool {
+        cell.cmpge(IVec2::ZERO).all() && cell.cmplt(self.size).all()
+    }
+
+    /// Where `cell` is in grids laid out row by row.
+    fn index(&self, cell: IVec2) -> usize {
+        (cell.y * self.size.x + cell.x) as usize
+    }
+
+    /// The state with `pieces`, and the player somewhere they can walk to from `player`.
+    fn state(&self, pieces: Vec<Piece>, player: IVec2) -> PuzzleState {
+        let player = self
+            .reachable(&pieces, None, player)
+            .into_iter()
+            .min_by_key(|&cell| cell_key(cell))
+            .unwrap_or(player);
+        PuzzleState { pieces, player }
+    }
+
+    /// The grid blueprints are evaluated on, with the pieces' walls added to the fixed layout.
+    fn grid(&self, pieces: &[Piece]) -> BlueprintGrid {
+        let mut grid = self.fixed.clone();
+        for piece in pieces {
+            for &(cell, is_wall) in &piece.items {
+                // Doors are checked last in the game, so they win over walls placed on them.
+                if is_wall && grid.get(&cell) != Some(&BlueprintCell::Door) {
+                    grid.insert(cell, BlueprintCell::Wall);
+                }
+            }
+        }
+        grid
+    }
+
+    fn evaluate(&self, grid: &BlueprintGrid, blueprint: usize) -> (BlueprintEvaluation, bool) {
+        let (location, requirements) = &self.blueprints[blueprint];
+        let evaluation = evaluate_blueprint(grid, *location);
+        let is_valid =
+            evaluation.is_valid() && requirements.check(&evaluation).iter().all(|(_, met)| *met);
+        (evaluation, is_valid)
+    }
+
+    /// How many blueprints are valid with the pieces where they are.
+    fn valid_blueprints(&self, pieces: &[Piece]) -> usize {
+        let grid = self.grid(pieces);
+        (0..self.blueprints.len())
+            .filter(|&blueprint| self.evaluate(&grid, blueprint).1)
+            .count()
+    }
+
+    /// Whether every blueprint is valid in `state`.
+    pub fn is_finished(&self, state: &PuzzleState) -> bool {
+        self.valid_blueprints(&state.pieces) == self.blueprints.len()
+    }
+
+    /// Plays `solution_move` from `state`, or returns `None` if the player can't make that move.
+    pub fn apply(&self, state: &PuzzleState, solution_move: Move) -> Option<PuzzleState> {
+        let Move {
+            pick_up,
+            quarter_turns,
+            place_at,
+            stand,
+        } = solution_move;
+        let index = state
+            .pieces
+            .iter()
+            .position(|piece| piece.items.iter().any(|&(cell, _)| cell == pick_up))?;
+        let reachable = self.reachable(&state.pieces, None, state.player);
+        if !NEIGHBORS
+            .iter()
+            .any(|&dir| reachable.contains(&(pick_up + dir)))
+        {
+            return None;
+        }
+        let offsets = state.pieces[index].turned(pick_up, quarter_turns);
+        let carrying = self.reachable(&state.pieces, Some(index), state.player);
+        let occupied = occupied_by_others(&state.pieces, index);
+        if !self
+            .stands(&offsets, place_at, &occupied, &carrying)
+            .contains(&stand)
+        {
+            return None;
+        }
+        let pieces = state.with_piece(index, Piece::placed(&offsets, place_at));
+        Some(self.state(pieces, stand))
+    }
+
+    /// The cells the player can walk to from `from`, ignoring the piece at `skip`.
+    fn reachable(&self, pieces: &[Piece], skip: Option<usize>, from: IVec2) -> HashSet<IVec2> {
+        // Cells are marked unwalkable once they are reached, so each is only queued once.
+        let mut walkable = self.walkable.clone();
+        for (index, piece) in pieces.iter().enumerate() {
+            if Some(index) == skip {
+                continue;
+            }
+            for &(cell, is_wall) in &piece.items {
+                if is_wall && self.in_bounds(cell) {
+                    walkable[self.index(cell)] = false;
+                }
+            }
+        }
+
+        let mut reached = HashSet::new();
+        let mut queue = VecDeque::new();
+        reached.insert(from);
+        queue.push_back(from);
+        if self.in_bounds(from) {
+            walkable[self.index(from)] = false;
+        }
+        while let Some(current) = queue.pop_front() {
+            for dir in [IVec2::X, IVec2::Y, IVec2::NEG_X, IVec2::NEG_Y] {
+                let neighbor = current + dir;
+                if self.in_bounds(neighbor) && walkable[self.index(neighbor)] {
+                    walkable[self.index(neighbor)] = false;
+                    reached.insert(neighbor);
+                    queue.push_back(neighbor);
+                }
+            }
+        }
+        reached
+    }
+
+    /// Finds the fewest moves that make every blueprint valid.
+    ///
+    /// This is a breadth-first search over every way of moving every piece the player can reach.
+    /// Within each number of moves, the states with the most valid blueprints are searched from
+    /// first, which finds an answer sooner without making it any longer.
+    pub fn solve(&self) -> SolveResult {
+        self.solve_within(MAX_SOLVER_STATES)
+    }
+
+    /// Like [`Puzzle::solve`], but gives up after visiting `max_states` states.
+    pub fn solve_within(&self, max_states: usize) -> SolveResult {
+        if self.is_finished(&self.start) {
+            return SolveResult::Solved(Vec::new());
+        }
+
+        // Each visited state, the state and move it was reached from, and how many blueprints are
+        // valid in it.
+        let mut nodes: Vec<(PuzzleState, Option<(usize, Move)>, usize)> =
+            vec![(self.start.clone(), None, 0)];
+        let mut visited: HashSet<PuzzleState> = HashSet::new();
+        visited.insert(self.start.clone());
+        let mut layer = vec![0];
+
+        while !layer.is_empty() {
+            layer.sort_by_key(|&index| std::cmp::Reverse(nodes[index].2));
+            let mut next_layer = Vec::new();
+            for index in layer {
+                let state = nodes[index].0.clone();
+                let grid = self.grid(&state.pieces);
+                // For each blueprint, the cells a wall must move into or out of to change it, and
+                // whether it is valid. These are the cells its flood fill looked at, except that a
+                // room open to the water stays open until a wall is put on its way out.
+                let evaluations: Vec<(HashSet<IVec2>, bool)> = (0..self.blueprints.len())
+                    .map(|blueprint| {
+                        let (evaluation, is_valid) = self.evaluate(&grid, blueprint);
+                        let cells = if evaluation.bounded_by_water {
+                            evaluation.leak_path
+                        } else {
+                            evaluation.region
+                        };
+                        (cells.into_iter().collect(), is_valid)
+                    })
+                    .collect();
+                for (next, next_move, moved_walls) in self.successors(&state) {
+                    if !visited.insert(next.clone()) {
+                        continue;
+                    }
+                    let mut next_grid = None;
+                    let valid = evaluations
+                        .iter()
+                        .enumerate()
+                        .filter(|(blueprint, (region, is_valid))| {
+                            if !moved_walls.iter().any(|cell| region.contains(cell)) {
+                                return *is_valid;
+                            }
+                            let next_grid =
+                                next_grid.get_or_insert_with(|| self.grid(&next.pieces));
+                            self.evaluate(next_grid, *blueprint).1
+                        })
+                        .count();
+                    nodes.push((next, Some((index, next_move)), valid));
+                    if valid == self.blueprints.len() {
+                        let mut path = Vec::new();
+                        let mut at = nodes.len() - 1;
+                        while let Some((parent, parent_move)) = nodes[at].1 {
+                            path.push(parent_move);
+                            at = parent;
+                        }
+                        path.reverse();
+                        return SolveResult::Solved(path);
+                    }
+                    if nodes.len() >= max_states {
+                        return SolveResult::GaveUp {
+                            states: nodes.len(),
+                        };
+                    }
+                    next_layer.push(nodes.len() - 1);
+                }
+            }
+            layer = next_layer;
+        }
+        SolveResult::Unsolvable
+    }
+
+    /// Every state one move away from `state`, along with the cells the move took walls out of or
+    /// put walls into.
+    fn successors(&self, state: &PuzzleState) -> Vec<(PuzzleState, Move, Vec<IVec2>)> {
+        let reachable = self.reachable(&state.pieces, None, state.player);
+        let mut successors = Vec::new();
+
+        for (index, piece) in state.pieces.iter().enumerate() {
+            let anchors: Vec<IVec2> = piece
+                .items
+                .iter()
+                .map(|&(cell, _)| cell)
+                .filter(|&cell| {
+                    NEIGHBORS
+                        .iter()
+                        .any(|&dir| reachable.contains(&(cell + dir)))
+                })
+                .collect();
+            if anchors.is_empty() {
+                continue;
+            }
+            // The player can walk through where the piece was, once it is picked up.
+            let carrying = self.reachable(&state.pieces, Some(index), state.player);
+            let occupied = occupied_by_others(&state.pieces, index);
+            let mut cursors: Vec<IVec2> = carrying
+                .iter()
+                .flat_map(|&cell| NEIGHBORS.map(|dir| cell + dir))
+                .collect();
+            cursors.sort_by_key(|&cursor| cell_key(cursor));
+            cursors.dedup();
+            let mut carrying_in_order: Vec<IVec2> = carrying.iter().copied().collect();
+            carrying_in_order.sort_by_key(|&cell| cell_key(cell));
+            // Each placement of the piece, and the places the player has been left in by putting it
+            // there. This is `None` when the piece's walls can't have split up where the player can
+            // walk, so there is only one place.
+            let mut placed: HashMap<Piece, Option<Vec<HashSet<IVec2>>>> = HashMap::new();
+
+            for &anchor in &anchors {
+                for quarter_turns in 0..4u8 {
+                    let offsets = piece.turned(anchor, quarter_turns);
+                    for &cursor in &cursors {
+                        let moved = Piece::placed(&offsets, cursor);
+                        if let Some(None) = placed.get(&moved) {
+                            continue;
+                        }
+                        let stands = self.stands(&offsets, cursor, &occupied, &carrying);
+                        if stands.is_empty() {
+                            continue;
+                        }
+                        let mut next = |pieces: Vec<Piece>, player: IVec2, stand: IVec2| {
+                            let moved_walls = piece
+                                .items
+                                .iter()
+                                .chain(&moved.items)
+                                .filter(|(_, is_wall)| *is_wall)
+                                .map(|&(cell, _)| cell)
+                                .collect();
+                            successors.push((
+                                PuzzleState { pieces, player },
+                                Move {
+                                    pick_up: anchor,
+                                    quarter_turns,
+                                    place_at: cursor,
+                                    stand,
+                                },
+                                moved_walls,
+                            ));
+                        };
+
+                        let regions = match placed.entry(moved.clone()) {
+                            Entry::Occupied(entry) => match entry.into_mut() {
+                                None => continue,
+                                Some(regions) => regions,
+                            },
+                            Entry::Vacant(entry) => {
+                                if stays_connected(&carrying, &moved) {
+                                    entry.insert(None);
+                                    let player = carrying_in_order
+                                        .iter()
+                                        .copied()
+                                        .find(|&cell| !moved.items.contains(&(cell, true)))
+                                        .unwrap_or(stands[0]);
+                                    next(state.with_piece(index, moved.clone()), player, stands[0]);
+                                    continue;
+                                }
+                                entry.insert(Some(Vec::new())).as_mut().unwrap()
+                            }
+                        };
+                        for stand in stands {
+                            if regions.iter().any(|region| region.contains(&stand)) {
+                                continue;
+                            }
+                            let pieces = state.with_piece(index, moved.clone());
+                            let region = self.reachable(&pieces, None, stand);
+                            let player = region
+                                .iter()
+                                .copied()
+                                .min_by_key(|&cell| cell_key(cell))
+                                .unwrap_or(stand);
+                            regions.push(region);
+                            next(pieces, player, stand);
+                        }
+                    }
+                }
+            }
+        }
+        successors
+    }
+
+    /// Where the player can stand to put a piece down with the cursor at `cursor`, if it fits there.
+    fn stands(
+        &self,
+        offsets: &[(IVec2, bool)],
+        cursor: IVec2,
+        occupied: &HashSet<IVec2>,
+        carrying: &HashSet<IVec2>,
+    ) -> Vec<IVec2> {
+        let fits = offsets.iter().all(|&(offset, _)| {
+            let cell = cursor + offset;
+            self.in_bounds(cell)
+                && !occupied.contains(&cell)
+                && !matches!(
+                    self.fixed.get(&cell),
+                    Some(BlueprintCell::Wall | BlueprintCell::Water)
+                )
+        });
+        if !fits {
+            return Vec::new();
+        }
+        NEIGHBORS
+            .iter()
+            .map(|&dir| cursor + dir)
+            .filter(|&stand| {
+                carrying.contains(&stand)
+                    && !offsets
+                        .iter()
+                        .any(|&(offset, is_wall)| is_wall && cursor + offset == stand)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::level_text::parse_level_text;
+
+    fn solve(text: &str) -> (Puzzle, SolveResult) {
+        let puzzle = Puzzle::new(&parse_level_text(text).unwrap(), &[]).unwrap();
+        let result = puzzle.solve();
+        (puzzle, result)
+    }
+
+    /// Plays `moves` from the start of `puzzle`, and checks that they finish it.
+    fn assert_finishes(puzzle: &Puzzle, moves: &[Move]) {
+        let mut state = puzzle.start.clone();
+        for &solution_move in moves {
+            state = puzzle
+                .apply(&state, solution_move)
+                .unwrap_or_else(|| panic!("can't play {:?}", solution_move));
+        }
+        assert!(puzzle.is_finished(&state));
+    }
+
+    #[test]
+    fn already_solved() {
+        let (_, result) = solve(
+            "[tiles]
+~~~~~~~
+~.....~
+~.###.~
+~.#B#.~
+~.#D#.~
+~@....~
+~~~~~~~
+",
+        );
+        assert!(matches!(result, SolveResult::Solved(moves) if moves.is_empty()));
+    }
+
+    #[test]
+    fn fills_a_gap_in_one_move() {
+        let (puzzle, result) = solve(
+            "[tiles]
+~~~~~~~
+~.....~
+~.#.#.~
+~.#B#.~
+~.#D#.~
+~@..f.~
+~~~~~~~
+",
+        );
+        let SolveResult::Solved(moves) = result else {
+            panic!("{:?}", result);
+        };
+        assert_eq!(moves.len(), 1);
+        assert_finishes(&puzzle, &moves);
+    }
+
+    #[test]
+    fn moves_two_glued_pieces() {
+        // The room is missing two cells of its top wall and two of its right wall, and each pair of
+        // fences is glued together.
+        let (puzzle, result) = solve(
+            "[tiles]
+~~~~~~~~~
+~.......~
+~.#..#..~
+~.#B....~
+~.#.....~
+~.#D##..~
+~.......~
+~@ff.ff.~
+~~~~~~~~~
+[glue]
+(2, 7), (3, 7)
+(5, 7), (6, 7)
+",
+        );
+        let SolveResult::Solved(moves) = result else {
+            panic!("{:?}", result);
+        };
+        assert_eq!(moves.len(), 2);
+        assert_finishes(&puzzle, &moves);
+    }
+
+    #[test]
+    fn gives_up_after_max_states() {
+        let level = parse_level_text(
+            "[tiles]
+~~~~~~~
+~.....~
+~.#.#.~
+~.#B#.~
+~.#D#.~
+~@..f.~
+~~~~~~~
+",
+        )
+        .unwrap();
+        let puzzle = Puzzle::new(&level, &[]).unwrap();
+        assert!(matches!(
+            puzzle.solve_within(3),
+            SolveResult::GaveUp { states: 3 }
+        ));
+    }
+
+    #[test]
+    fn nothing_to_fill_the_gap_with() {
+        let (_, result) = solve(
+            "[tiles]
+~~~~~~~
+~.....~
+~.#.#.~
+~.#B#.~
+~.#D#.~
+~@....~
+~~~~~~~
+",
+        );
+        assert!(matches!(result, SolveResult::Unsolvable));
+    }
+}
@@ -0,0 +1,401 @@
+use bevy::prelude::*;
+use serde::Deserialize;
+
+use crate::{
+    blueprint::{ActiveBlueprint, BlueprintStatus},
+    controls::{Action, ActionMap, ActionState, Binding, game_running},
+    item::Item,
+    player::Player,
+};
+
+/// The tutorial's steps, in the order they are shown.
+pub const TUTORIAL_PATH: &str = "assets/tutorial.ron";
+
+pub struct TutorialPlugin;
+
+impl Plugin for TutorialPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Hint>()
+            .add_systems(Startup, setup_tutorial_system)
+            .add_systems(
+                Update,
+                (
+                    (advance_tutorial_system, hint_system).run_if(game_running),
+                    show_tutorial_system,
+                )
+                    .chain(),
+            );
+    }
+}
+
+/// How close an item must be to the player for [`TutorialTrigger::NearItem`].
+const NEAR_ITEM_DISTANCE: f32 = 1.5;
+/// How long a hint stays on screen.
+const HINT_SECONDS: f32 = 6.;
+
+#[derive(Deserialize, Clone, Debug)]
+pub enum TutorialTrigger {
+    Always,
+    /// The player is walking.
+    Moving,
+    /// An item the player isn't holding is close by.
+    NearItem,
+    /// The player is holding a group of items.
+    HoldingGroup,
+    /// The player is standing near a blueprint.
+    BlueprintActive,
+    /// The blueprint the player is standing near is valid.
+    BlueprintValid,
+    /// The action was pressed this frame.
+    Pressed(Action),
+    Not(Box<TutorialTrigger>),
+}
+
+/// The game state which tutorial triggers can check.
+pub struct TutorialContext<'a> {
+    pub moving: bool,
+    pub near_item: bool,
+    pub holding_group: bool,
+    pub blueprint_active: bool,
+    pub blueprint_valid: bool,
+    pub action_state: &'a ActionState,
+}
+
+impl TutorialTrigger {
+    pub fn holds(&self, context: &TutorialContext) -> bool {
+        match self {
+            TutorialTrigger::Always => true,
+            TutorialTrigger::Moving => context.moving,
+            TutorialTrigger::NearItem => context.near_item,
+            TutorialTrigger::HoldingGroup => context.holding_group,
+            TutorialTrigger::BlueprintActive => context.blueprint_active,
+            TutorialTrigger::BlueprintValid => context.blueprint_valid,
+            TutorialTrigger::Pressed(action) => context.action_state.just_pressed(*action),
+            TutorialTrigger::Not(trigger) => !trigger.holds(context),
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct TutorialStep {
+    /// The step's prompt is shown once this holds.
+    pub show_when: TutorialTrigger,
+    /// The step is finished once this holds while its prompt is shown.
+    pub done_when: TutorialTrigger,
+    /// `{Action}` is replaced with the action's first binding, like `{Interact}`.
+    pub prompt: String,
+}
+
+#[derive(Resource, Default)]
+pub struct Tutorial {
+    pub steps: Vec<TutorialStep>,
+    /// The index of the step being worked on.
+    pub current: usize,
+    /// Whether the current step's prompt is showing.
+    pub showing: bool,
+}
+
+impl Tutorial {
+    pub fn load(path: &str) -> Result<Tutorial, Box<dyn std::error::Error>> {
+        let text = std::fs::read_to_string(path)?;
+        Ok(Tutorial {
+            steps: ron::from_str(&text)?,
+            current: 0,
+            showing: false,
+        })
+    }
+}
+
+/// A hint about the active blueprint, shown for a few seconds after the hint action is pressed.
+#[derive(Resource, Default)]
+pub struct Hint {
+    pub text: String,
+    pub remaining: f32,
+}
+
+#[derive(Component)]
+pub struct TutorialBox;
+
+#[derive(Component)]
+pub struct TutorialText;
+
+pub fn setup_tutorial_system(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let tutorial = match Tutorial::load(TUTORIAL_PATH) {
+        Ok(tutorial) => tutorial,
+        Err(err) => {
+            warn!("no tutorial: cannot load {}: {}", TUTORIAL_PATH, err);
+            Tutorial::default()
+        }
+    };
+    commands.insert_resource(tutorial);
+
+    let font = asset_server.load("fonts/delius/Delius-Regular.ttf");
+    commands
+        .spawn(Node {
+            position_type: PositionType::Absolute,
+            width: percent(100),
+            justify_content: JustifyContent::Center,
+            margin: UiRect::top(px(60)),
+            ..default()
+        })
+        .with_children(|builder| {
+            builder
+                .spawn((
+                    TutorialBox,
+                    Node {
+                        display: Display::None,
+                        max_width: px(700),
+                        padding: UiRect::axes(px(20), px(10)),
+                        ..default()
+                    },
+                    BackgroundColor(Color::linear_rgba(0.05, 0.05, 0.15, 0.8)),
+                ))
+                .with_children(|builder| {
+                    builder.spawn((
+                        TutorialText,
+                        Text::default(),
+                        TextColor(Color::linear_rgb(1., 1., 1.)),
+                        TextFont {
+                            font,
+                            font_size: 25.0,
+                            ..default()
+                        },
+                    ));
+                });
+        });
+}
+
+/// What to call a binding in a prompt.
+fn binding_name(binding: &Binding) -> String {
+    match binding {
+        Binding::Key(key) => {
+            let name = format!("{:?}", key);
+            name.trim_start_matches("Key")
+                .trim_start_matches("Digit")
+                .to_string()
+        }
+        Binding::Gamepad(button) => format!("{:?}", button),
+        Binding::Mouse(button) => format!("{:?} click", button),
+    }
+}
+
+/// Replaces each `{Action}` in `text` with the action's first binding.
+pub fn fill_in_bindings(text: &str, action_map: &ActionMap) -> String {
+    let mut text = text.to_string();
+    for (action, bindings) in &action_map.bindings {
+        let Some(binding) = bindings.first() else {
+            continue;
+        };
+        text = text.replace(&format!("{{{:?}}}", action), &binding_name(binding));
+    }
+    text
+}
+
+/// The first thing the room is missing, or `None` if it is done.
+pub fn missing_requirement(status: &BlueprintStatus) -> Option<String> {
+    let evaluation = &status.evaluation;
+    if evaluation.bounded_by_water {
+        return Some("The room is open to the water. Close the gap the arrow points to.".into());
+    }
+    if evaluation.too_big {
+        return Some("The room isn't closed off. Follow the arrow to find the gap.".into());
+    }
+    if !evaluation.has_door {
+        return Some("The room needs a door in one of its walls.".into());
+    }
+    status
+        .requirements
+        .iter()
+        .find(|(_, met)| !met)
+        .map(|(description, _)| format!("The client wants: {}.", description))
+}
+
+pub fn advance_tutorial_system(
+    mut tutorial: ResMut<Tutorial>,
+    action_state: Res<ActionState>,
+    active_blueprint: Res<ActiveBlueprint>,
+    statuses: Query<&BlueprintStatus>,
+    player: Query<(&Transform, &Player)>,
+    items: Query<(&Transform, &Item)>,
+) {
+    let Some(step) = tutorial.steps.get(tutorial.current) else {
+        return;
+    };
+    let Ok((player_transform, player)) = player.single() else {
+        return;
+    };
+    let active_status = active_blueprint
+        .active_blueprint
+        .as_ref()
+        .and_then(|active| statuses.get(active.blueprint_entity).ok());
+    let context = TutorialContext {
+        moving: player.velocity.length() > 0.1,
+        near_item: items.iter().any(|(item_transform, item)| {
+            item.is_held.is_none()
+                && item_transform
+                    .translation
+                    .xz()
+                    .distance(player_transform.translation.xz())
+                    < NEAR_ITEM_DISTANCE
+        }),
+        holding_group: items.iter().any(|(_, item)| item.is_held.is_some()),
+        blueprint_active: active_status.is_some(),
+        blueprint_valid: active_status.is_some_and(|status| status.is_valid()),
+        action_state: &action_state,
+    };
+
+    if !tutorial.showing {
+        if step.show_when.holds(&context) {
+            tutorial.showing = true;
+        }
+    } else if step.done_when.holds(&context) {
+        tutorial.current += 1;
+        tutorial.showing = false;
+    }
+}
+
+pub fn hint_system(
+    time: Res<Time>,
+    action_state: Res<ActionState>,
+    active_blueprint: Res<ActiveBlueprint>,
+    statuses: Query<&BlueprintStatus>,
+    mut hint: ResMut<Hint>,
+) {
+    hint.remaining = (hint.remaining - time.delta_secs()).max(0.);
+    if !action_state.just_pressed(Action::Hint) {
+        return;
+    }
+    let active_status = active_blueprint
+        .active_blueprint
+        .as_ref()
+        .and_then(|active| statuses.get(active.blueprint_entity).ok());
+    hint.text = match active_status {
+        None => "Stand next to a blueprint to see what its room needs.".to_string(),
+        Some(status) => missing_requirement(status)
+            .unwrap_or_else(|| "This room has everything it needs!".to_string()),
+    };
+    hint.remaining = HINT_SECONDS;
+}
+
+pub fn show_tutorial_system(
+    tutorial: Res<Tutorial>,
+    hint: Res<Hint>,
+    action_map: Res<ActionMap>,
+    mut tutorial_box: Query<&mut Node, With<TutorialBox>>,
+    mut tutorial_text: Query<&mut Text, With<TutorialText>>,
+) {
+    let (Ok(mut node), Ok(mut text)) = (tutorial_box.single_mut(), tutorial_text.single_mut())
+    else {
+        return;
+    };
+    // Hints take the place of the tutorial's prompt while they are shown.
+    let prompt = if hint.remaining > 0. {
+        Some(hint.text.clone())
+    } else {
+        tutorial
+            .steps
+            .get(tutorial.current)
+            .filter(|_| tutorial.showing)
+            .map(|step| fill_in_bindings(&step.prompt, &action_map))
+    };
+    match prompt {
+        Some(prompt) => {
+            node.display = Display::Flex;
+            if text.0 != prompt {
+                text.0 = prompt;
+            }
+        }
+        None => node.display = Display::None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blueprint::BlueprintEvaluation;
+
+    fn context(action_state: &ActionState) -> TutorialContext<'_> {
+        TutorialContext {
+            moving: true,
+            near_item: false,
+            holding_group: true,
+            blueprint_active: true,
+            blueprint_valid: false,
+            action_state,
+        }
+    }
+
+    #[test]
+    fn triggers_check_the_context() {
+        let action_state = ActionState::default();
+        let context = context(&action_state);
+        assert!(TutorialTrigger::Always.holds(&context));
+        assert!(TutorialTrigger::Moving.holds(&context));
+        assert!(!TutorialTrigger::NearItem.holds(&context));
+        assert!(TutorialTrigger::HoldingGroup.holds(&context));
+        assert!(TutorialTrigger::BlueprintActive.holds(&context));
+        assert!(!TutorialTrigger::BlueprintValid.holds(&context));
+        assert!(!TutorialTrigger::Pressed(Action::Interact).holds(&context));
+    }
+
+    #[test]
+    fn not_inverts_its_trigger() {
+        let action_state = ActionState::default();
+        let context = context(&action_state);
+        let not = |trigger| TutorialTrigger::Not(Box::new(trigger));
+        assert!(!not(TutorialTrigger::Always).holds(&context));
+        assert!(not(TutorialTrigger::NearItem).holds(&context));
+        assert!(not(TutorialTrigger::Pressed(Action::Interact)).holds(&context));
+        assert!(!not(not(TutorialTrigger::NearItem)).holds(&context));
+    }
+
+    #[test]
+    fn bindings_are_filled_in() {
+        let mut action_map = ActionMap::default();
+        action_map.bindings.insert(
+            Action::Interact,
+            vec![
+                Binding::Mouse(MouseButton::Left),
+                Binding::Key(KeyCode::KeyE),
+            ],
+        );
+        action_map
+            .bindings
+            .insert(Action::Rotate, vec![Binding::Gamepad(GamepadButton::West)]);
+        action_map.bindings.insert(Action::Hint, vec![]);
+        assert_eq!(
+            fill_in_bindings(
+                "{Interact} to pick up, {Rotate} to turn, {Hint} for help, {Jump}.",
+                &action_map
+            ),
+            "Left click to pick up, West to turn, {Hint} for help, {Jump}."
+        );
+    }
+
+    #[test]
+    fn first_missing_requirement_is_reported() {
+        let mut status = BlueprintStatus {
+            evaluation: BlueprintEvaluation {
+                has_door: true,
+                ..default()
+            },
+            requirements: vec![("a bed".to_string(), true), ("a lamp".to_string(), false)],
+        };
+        assert_eq!(
+            missing_requirement(&status).as_deref(),
+            Some("The client wants: a lamp.")
+        );
+
+        status.requirements[1].1 = true;
+        assert_eq!(missing_requirement(&status), None);
+
+        status.evaluation.has_door = false;
+        assert!(missing_requirement(&status).unwrap().contains("door"));
+
+        // A room that isn't closed is reported before anything else.
+        status.evaluation.too_big = true;
+        assert!(missing_requirement(&status).unwrap().contains("closed off"));
+        status.evaluation.bounded_by_water = true;
+        assert!(missing_requirement(&status).unwrap().contains("water"));
+    }
+}